@@ -1,49 +1,405 @@
-use crate::grid::apoint::APoint;
-use crate::grid::grid_2d::Grid2D;
-use crate::rule::Rules;
-use crate::state::State;
-
-/// struct containing all the cells, rules / transitions, and cells that need 
-/// to be updated for the current step 
-pub struct Automaton2D<'a, T, U> 
-    where 
-    U: Fn(State<T>) -> State<T>, {
-    grid: Grid2D<'a, APoint<'a, i32, State<'a, T>>, i32>, 
-    rules: Rules<State<'a, T>, U>,
-    update_cells:  Vec<&'a T>
+pub mod layered;
+pub mod recording;
+pub mod schedule;
+pub mod snapshot;
+
+use crate::errors::CaError;
+use crate::grid::dense_grid::{Boundary, DenseGrid};
+use crate::grid::point::Point;
+use crate::rule::lookup::LookupTable;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::{State, StateSet};
+use schedule::Schedule;
+
+/// struct containing all the cells and the transitions used to step them.
+/// Rules small enough are compiled into a [`LookupTable`] which is used in
+/// their place
+pub struct Automaton2D<R>
+where
+    R: Rule, {
+    grid: DenseGrid<State>,
+    next: Vec<State>,
+    rules: R,
+    states: StateSet,
+    table: Option<LookupTable>,
+    generation: u64,
+    rng_seed: u64,
+    schedule: Schedule,
 }
 
-impl<'a, T, U> Automaton2D<'a, T, U>
+impl<R> Automaton2D<R>
 where
-    U: Fn(State<T>) -> State<T>, {
-    
-    /// Return a new `Automaton2D` Struct 
-    pub fn new() -> Automaton2D<'a, T, U> {
+    R: Rule, {
+
+    /// Return a new `Automaton2D` Struct with every cell in state 0 and no
+    /// named states
+    pub fn new(width: usize, height: usize, rules: R) -> Automaton2D<R> {
         Automaton2D {
-            grid: Grid2D::new(),
-            rules: Rules::new(),
-            update_cells: Vec::new(),
+            grid: DenseGrid::new(width, height),
+            next: Vec::new(),
+            table: LookupTable::compile(&rules),
+            rules,
+            states: StateSet::new(),
+            generation: 0,
+            rng_seed: 0,
+            schedule: Schedule::Synchronous,
         }
     }
 
-    /// Set the initial points grid
-    pub fn set_seed(&mut self, seed: Vec<&'a APoint<'a, i32, State<'a, T>>>){
-        self.grid.set_points(seed);
+    /// Set the initial points grid, every cell not in `seed` is set to state 0.
+    /// Fails without changing the grid if a point is outside of a fixed grid
+    pub fn set_seed(&mut self, seed: &[(Point<i32>, State)]) -> Result<(), CaError> {
+        if let Some((point, _)) = seed.iter().find(|(p, _)| self.grid.index(*p).is_none()) {
+            return Err(CaError::OutOfBounds {
+                point: *point,
+                width: self.grid.width(),
+                height: self.grid.height(),
+            });
+        }
+        self.grid.clear();
+        for (p, s) in seed {
+            self.grid.set(*p, *s);
+        }
+        self.generation = 0;
+        Ok(())
     }
 
     /// set the rules to the struct containing transitions for each state
-    pub fn set_rules(&mut self, ruleset: Rules<State<'a, T>, U>) {
+    pub fn set_rules(&mut self, ruleset: R) {
+        self.table = LookupTable::compile(&ruleset);
         self.rules = ruleset;
     }
 
-    /// Add cell to vector of cells that need to be updated at the next step
-    pub fn add_ucell(&mut self, cell: &'a T) {
-        self.update_cells.push(cell);
+    pub fn rules(&self) -> &R {
+        &self.rules
+    }
+
+    /// Names of the states cells can be in
+    pub fn states(&self) -> &StateSet {
+        &self.states
+    }
+
+    pub fn set_states(&mut self, states: StateSet) {
+        self.states = states;
+    }
+
+    /// The table the rules were compiled into, `None` if they are too large
+    /// to tabulate
+    pub fn table(&self) -> Option<&LookupTable> {
+        self.table.as_ref()
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.grid.set_boundary(boundary);
+    }
+
+    pub fn grid(&self) -> &DenseGrid<State> {
+        &self.grid
+    }
+
+    pub fn grid_mut(&mut self) -> &mut DenseGrid<State> {
+        &mut self.grid
+    }
+
+    /// Number of steps executed since the seed was set
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Seed every cell's random stream is derived from, 0 by default
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    /// Set the order cells are updated in, [`Schedule::Synchronous`] by
+    /// default
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+
+    /// Next state of the cell at `index`, `neighbours` is a buffer as long as
+    /// the neighbourhood
+    fn next_state(&self, index: usize, neighbours: &mut [State]) -> State {
+        let p = self.grid.point(index);
+        let state = self.grid.cells()[index];
+        if !self.schedule.updates(self.rng_seed, p, self.generation) {
+            return state;
+        }
+        for (n, o) in neighbours.iter_mut().zip(self.rules.neighbourhood()) {
+            *n = self.grid.get(p + *o).copied().unwrap_or_default();
+        }
+        if let Some(next) = self.table.as_ref().and_then(|t| t.get(state, neighbours)) {
+            return next;
+        }
+        let mut rng = Rng::for_cell(self.rng_seed, p, self.generation);
+        self.rules.apply(state, neighbours, p, self.generation, &mut rng)
+    }
+
+    /// Update the cells in the order given by the schedule. Neighbours
+    /// outside of a fixed grid are in state 0
+    pub fn step(&mut self) {
+        let mut neighbours = vec![State::default(); self.rules.neighbourhood().len()];
+        if self.schedule.is_sequential() {
+            for i in self.schedule.order(self.rng_seed, self.generation, self.grid.cells().len()) {
+                self.grid.cells_mut()[i] = self.next_state(i, &mut neighbours);
+            }
+            self.generation += 1;
+            return;
+        }
+        let mut next = std::mem::take(&mut self.next);
+        next.clear();
+        next.extend((0..self.grid.cells().len()).map(|i| self.next_state(i, &mut neighbours)));
+        self.next = next;
+        self.grid.cells_mut().swap_with_slice(&mut self.next);
+        self.generation += 1;
+    }
+
+    /// Same as [`Automaton2D::step`] with the rows split between `threads`
+    /// threads. Each cell draws from its own random stream so the result
+    /// doesn't depend on the number of threads. Sequential schedules run on
+    /// the current thread
+    pub fn step_parallel(&mut self, threads: usize)
+    where
+        R: Sync, {
+        if self.schedule.is_sequential() {
+            return self.step();
+        }
+        let cells = self.grid.cells().len();
+        let width = self.grid.width().max(1);
+        let rows_per_thread = self.grid.height().div_ceil(threads.max(1)).max(1);
+        let mut next = std::mem::take(&mut self.next);
+        next.clear();
+        next.resize(cells, State::default());
+        let this = &*self;
+        std::thread::scope(|scope| {
+            for (chunk_index, chunk) in next.chunks_mut(rows_per_thread * width).enumerate() {
+                scope.spawn(move || {
+                    let mut neighbours = vec![State::default(); this.rules.neighbourhood().len()];
+                    let start = chunk_index * rows_per_thread * width;
+                    for (i, cell) in chunk.iter_mut().enumerate() {
+                        *cell = this.next_state(start + i, &mut neighbours);
+                    }
+                });
+            }
+        });
+        self.next = next;
+        self.grid.cells_mut().swap_with_slice(&mut self.next);
+        self.generation += 1;
+    }
+
+    /// Execute `steps` steps
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::IsPoint;
+    use crate::rule::builder::RuleBuilder;
+    use crate::rule::Rules;
+
+    /// Every cell takes the state of its western neighbour
+    struct ShiftEast(Vec<Point<i32>>);
+
+    impl Rule for ShiftEast {
+        fn neighbourhood(&self) -> &[Point<i32>] {
+            &self.0
+        }
+
+        fn apply(&self, _state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+            neighbours[0]
+        }
+    }
+
+    fn ids<const N: usize>(ids: [u8; N]) -> [State; N] {
+        ids.map(State::new)
+    }
+
+    #[test]
+    fn step_test() {
+        let mut automaton = Automaton2D::new(3, 1, ShiftEast(vec![Point::new(-1, 0)]));
+        automaton.set_seed(&[(Point::new(0, 0), State::new(1))]).unwrap();
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([0, 1, 0]));
+        automaton.run(2);
+        assert_eq!(automaton.grid().cells(), &ids([0, 0, 0]));
+        assert_eq!(automaton.generation(), 3);
+    }
+
+    /// Cells in the column matching the generation are in state 1
+    struct Sweep;
+
+    impl Rule for Sweep {
+        fn neighbourhood(&self) -> &[Point<i32>] {
+            &[]
+        }
+
+        fn apply(&self, _state: State, _neighbours: &[State], point: Point<i32>, generation: u64, _rng: &mut Rng) -> State {
+            State::new((point.x() as u64 == generation) as u8)
+        }
+
+        fn n_states(&self) -> Option<usize> {
+            Some(2)
+        }
+    }
+
+    #[test]
+    fn context_step_test() {
+        let mut automaton = Automaton2D::new(3, 2, Sweep);
+        assert!(automaton.table().is_none());
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([1, 0, 0, 1, 0, 0]));
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([0, 1, 0, 0, 1, 0]));
+    }
+
+    #[test]
+    fn table_step_test() {
+        let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+        rules.insert(State::new(0), |n| n[0]);
+        rules.insert(State::new(1), |n| n[0]);
+        rules.set_neighbourhood(vec![Point::new(-1, 0)]);
+        rules.set_n_states(Some(2));
+        let mut automaton = Automaton2D::new(3, 1, rules);
+        assert_eq!(automaton.table().map(|t| t.len()), Some(4));
+
+        // state 5 isn't in the table so falls back to the rules
+        automaton.set_seed(&[(Point::new(0, 0), State::new(1)), (Point::new(1, 0), State::new(5))]).unwrap();
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([0, 5, 5]));
+    }
+
+    #[test]
+    fn stochastic_step_test() {
+        let mut states = StateSet::new();
+        for name in ["empty", "tree", "fire"] {
+            states.push(name);
+        }
+        let forest_fire = RuleBuilder::new()
+            .when("empty").with_probability(0.05).then("tree")
+            .when("tree").count("fire").at_least(1).then("fire")
+            .when("tree").with_probability(0.001).then("fire")
+            .when("fire").then("empty")
+            .build(&states)
+            .unwrap();
+        let run = |seed, threads| {
+            let mut automaton = Automaton2D::new(16, 9, forest_fire.clone());
+            automaton.set_rng_seed(seed);
+            assert!(automaton.table().is_none());
+            for _ in 0..30 {
+                match threads {
+                    0 => automaton.step(),
+                    n => automaton.step_parallel(n),
+                }
+            }
+            automaton.grid().cells().to_vec()
+        };
+        let sequential = run(3, 0);
+        assert!(sequential.iter().any(|s| s.id() == 1));
+        for threads in [1, 2, 4, 20] {
+            assert_eq!(run(3, threads), sequential);
+        }
+        assert_ne!(run(4, 0), sequential);
+    }
+
+    /// Every cell counts how many times it was updated
+    struct Counter;
+
+    impl Rule for Counter {
+        fn neighbourhood(&self) -> &[Point<i32>] {
+            &[]
+        }
+
+        fn apply(&self, state: State, _neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+            State::new(state.id() + 1)
+        }
+    }
+
+    fn spread_east() -> Rules<State, fn(&[State]) -> State> {
+        let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+        rules.insert(State::new(0), |n| n[0]);
+        rules.set_neighbourhood(vec![Point::new(-1, 0)]);
+        rules
+    }
+
+    #[test]
+    fn sequential_schedule_test() {
+        let mut automaton = Automaton2D::new(4, 1, spread_east());
+        automaton.set_seed(&[(Point::new(0, 0), State::new(1))]).unwrap();
+        automaton.set_schedule(Schedule::Sweep);
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([1, 1, 1, 1]));
+
+        let run = |seed| {
+            let mut automaton = Automaton2D::new(12, 12, spread_east());
+            let seed_cells: Vec<_> = (0..12).map(|y| (Point::new(0, y), State::new(1))).collect();
+            automaton.set_seed(&seed_cells).unwrap();
+            automaton.set_schedule(Schedule::RandomSequential);
+            automaton.set_rng_seed(seed);
+            automaton.step_parallel(4);
+            automaton.grid().cells().to_vec()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+        assert!(run(1).iter().filter(|s| s.id() == 1).count() > 24);
+
+        let mut automaton = Automaton2D::new(8, 8, Counter);
+        automaton.set_schedule(Schedule::RandomSequential);
+        automaton.run(3);
+        assert!(automaton.grid().cells().iter().all(|s| s.id() == 3));
+    }
+
+    #[test]
+    fn selective_schedule_test() {
+        let mut automaton = Automaton2D::new(20, 20, Counter);
+        automaton.set_schedule(Schedule::RandomIndependent(0.25));
+        automaton.run(4);
+        let updates: usize = automaton.grid().cells().iter().map(|s| s.id() as usize).sum();
+        assert!((300..500).contains(&updates));
+        let mut parallel = Automaton2D::new(20, 20, Counter);
+        parallel.set_schedule(Schedule::RandomIndependent(0.25));
+        (0..4).for_each(|_| parallel.step_parallel(3));
+        assert_eq!(parallel.grid().cells(), automaton.grid().cells());
+
+        let mut automaton = Automaton2D::new(20, 20, Counter);
+        automaton.set_schedule(Schedule::Clocked { max_period: 4 });
+        automaton.run(12);
+        let counts = automaton.grid().cells();
+        assert!(counts.iter().all(|s| [3, 4, 6, 12].contains(&s.id())));
+        assert!(counts.iter().any(|s| s.id() == 3) && counts.iter().any(|s| s.id() == 12));
+    }
+
+    #[test]
+    fn seed_out_of_bounds_test() {
+        let mut automaton = Automaton2D::new(3, 1, ShiftEast(vec![Point::new(-1, 0)]));
+        automaton.set_seed(&[(Point::new(1, 0), State::new(1))]).unwrap();
+        let err = automaton.set_seed(&[(Point::new(0, 0), State::new(1)), (Point::new(3, 0), State::new(1))]);
+        assert_eq!(err.unwrap_err().to_string(), "(3, 0) is outside of the 3x1 grid");
+        assert_eq!(automaton.grid().cells(), &ids([0, 1, 0]));
+
+        automaton.set_boundary(Boundary::Toroidal);
+        assert!(automaton.set_seed(&[(Point::new(3, 0), State::new(1))]).is_ok());
     }
 
-    /// remove all cells from the update Vec, Usually done after executing a 
-    /// step
-    pub fn clear_ucells(&mut self) {
-        self.update_cells.clear();
+    #[test]
+    fn toroidal_step_test() {
+        let mut automaton = Automaton2D::new(3, 1, ShiftEast(vec![Point::new(-1, 0)]));
+        automaton.set_boundary(Boundary::Toroidal);
+        automaton.set_seed(&[(Point::new(2, 0), State::new(1))]).unwrap();
+        automaton.step();
+        assert_eq!(automaton.grid().cells(), &ids([1, 0, 0]));
     }
 }
@@ -0,0 +1,237 @@
+use crate::grid::layers::{LayerId, Layers};
+use crate::grid::point::Point;
+use crate::rng::Rng;
+
+/// Rule of a [`LayeredAutomaton`], reading any layer around a cell and
+/// writing any layer of the cell. Layers that aren't written keep their value
+pub trait LayerRule {
+    fn apply(&self, cell: &Cell<'_>, next: &mut Next<'_>);
+}
+
+impl<F> LayerRule for F
+where
+    F: Fn(&Cell<'_>, &mut Next<'_>), {
+    fn apply(&self, cell: &Cell<'_>, next: &mut Next<'_>) {
+        self(cell, next)
+    }
+}
+
+/// Read access to the layers around the cell being updated
+pub struct Cell<'a> {
+    layers: &'a Layers,
+    point: Point<i32>,
+    index: usize,
+    generation: u64,
+    rng_seed: u64,
+}
+
+impl<'a> Cell<'a> {
+    pub fn point(&self) -> Point<i32> {
+        self.point
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Value of the cell in a layer
+    pub fn get<T: 'static>(&self, id: LayerId<T>) -> &'a T {
+        &self.layers.get(id).cells()[self.index]
+    }
+
+    /// Value of the cell at `offset` from this one, `None` outside of a fixed
+    /// grid
+    pub fn neighbour<T: 'static>(&self, id: LayerId<T>, offset: Point<i32>) -> Option<&'a T> {
+        self.layers.get(id).get(self.point + offset)
+    }
+
+    /// Values of the cells at `offsets` from this one, the default value
+    /// outside of a fixed grid
+    pub fn neighbours<'b, T>(&'b self, id: LayerId<T>, offsets: &'b [Point<i32>]) -> impl Iterator<Item = T> + 'b
+    where
+        T: Copy + Default + 'static, {
+        offsets.iter().map(move |o| self.neighbour(id, *o).copied().unwrap_or_default())
+    }
+
+    /// The cell's random stream for this generation
+    pub fn rng(&self) -> Rng {
+        Rng::for_cell(self.rng_seed, self.point, self.generation)
+    }
+}
+
+/// Write access to the next values of the cell being updated
+pub struct Next<'a> {
+    layers: &'a mut Layers,
+    index: usize,
+}
+
+impl Next<'_> {
+    pub fn set<T: 'static>(&mut self, id: LayerId<T>, value: T) {
+        self.layers.get_mut(id).cells_mut()[self.index] = value;
+    }
+}
+
+/// Automaton over several [`Layers`] updated at once by a [`LayerRule`]
+///
+/// # Examples
+///
+/// ```
+/// use ca::automaton::layered::{Cell, LayeredAutomaton, Next};
+/// use ca::grid::layers::Layers;
+/// use ca::grid::point::Point;
+///
+/// // Gray-Scott reaction-diffusion
+/// let mut layers = Layers::new(16, 16);
+/// let u = layers.add::<f64>("u").unwrap();
+/// let v = layers.add::<f64>("v").unwrap();
+/// layers.get_mut(u).fill(1.0);
+/// layers.get_mut(v).set(Point::new(8, 8), 1.0);
+///
+/// let around = [Point::new(0, -1), Point::new(-1, 0), Point::new(1, 0), Point::new(0, 1)];
+/// let rule = move |cell: &Cell, next: &mut Next| {
+///     let laplace = |id| cell.neighbours(id, &around).sum::<f64>() - 4.0 * cell.get(id);
+///     let (a, b) = (*cell.get(u), *cell.get(v));
+///     let reaction = a * b * b;
+///     next.set(u, a + 0.2 * laplace(u) - reaction + 0.04 * (1.0 - a));
+///     next.set(v, b + 0.1 * laplace(v) + reaction - 0.1 * b);
+/// };
+/// let mut ca = LayeredAutomaton::new(layers, rule);
+/// ca.run(10);
+/// assert!(*ca.layers().get(v).get(Point::new(9, 8)).unwrap() > 0.0);
+/// ```
+pub struct LayeredAutomaton<R>
+where
+    R: LayerRule, {
+    layers: Layers,
+    next: Option<Layers>,
+    rule: R,
+    generation: u64,
+    rng_seed: u64,
+}
+
+impl<R> LayeredAutomaton<R>
+where
+    R: LayerRule, {
+    pub fn new(layers: Layers, rule: R) -> LayeredAutomaton<R> {
+        LayeredAutomaton {
+            layers,
+            next: None,
+            rule,
+            generation: 0,
+            rng_seed: 0,
+        }
+    }
+
+    pub fn layers(&self) -> &Layers {
+        &self.layers
+    }
+
+    /// Layers to seed or add to, resets the generation
+    pub fn layers_mut(&mut self) -> &mut Layers {
+        self.next = None;
+        self.generation = 0;
+        &mut self.layers
+    }
+
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: R) {
+        self.rule = rule;
+    }
+
+    /// Number of steps executed since the layers were last changed
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Seed every cell's random stream is derived from, 0 by default
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+    }
+
+    /// Apply the rule to every cell from the values of the previous step
+    pub fn step(&mut self) {
+        let mut next = match self.next.take() {
+            Some(mut next) => {
+                next.copy_from(&self.layers);
+                next
+            }
+            None => self.layers.clone(),
+        };
+        let width = self.layers.width();
+        for index in 0..width * self.layers.height() {
+            let cell = Cell {
+                layers: &self.layers,
+                point: Point::new((index % width) as i32, (index / width) as i32),
+                index,
+                generation: self.generation,
+                rng_seed: self.rng_seed,
+            };
+            self.rule.apply(&cell, &mut Next { layers: &mut next, index });
+        }
+        self.next = Some(std::mem::replace(&mut self.layers, next));
+        self.generation += 1;
+    }
+
+    /// Execute `steps` steps
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::dense_grid::Boundary;
+    use crate::state::State;
+
+    #[test]
+    fn diffusion_test() {
+        let mut layers = Layers::new(5, 5);
+        layers.set_boundary(Boundary::Toroidal);
+        let heat = layers.add::<f64>("heat").unwrap();
+        layers.get_mut(heat).set(Point::new(2, 2), 1.0);
+        let around = [Point::new(0, -1), Point::new(-1, 0), Point::new(1, 0), Point::new(0, 1)];
+        let mut ca = LayeredAutomaton::new(layers, move |cell: &Cell, next: &mut Next| {
+            let mean = cell.neighbours(heat, &around).sum::<f64>() / 4.0;
+            next.set(heat, 0.5 * cell.get(heat) + 0.5 * mean);
+        });
+        ca.run(4);
+        let grid = ca.layers().get(heat);
+        assert!((grid.cells().iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(grid.cells().iter().all(|v| *v > 0.0));
+    }
+
+    #[test]
+    fn cross_layer_test() {
+        // cells age while alive, and die once they reach age 2
+        let mut layers = Layers::new(3, 1);
+        let alive = layers.add::<State>("alive").unwrap();
+        let age = layers.add::<u32>("age").unwrap();
+        layers.get_mut(alive).cells_mut().copy_from_slice(&[State::new(1), State::new(0), State::new(1)]);
+        layers.get_mut(age).set(Point::new(2, 0), 1);
+        let mut ca = LayeredAutomaton::new(layers, move |cell: &Cell, next: &mut Next| {
+            if *cell.get(alive) == State::new(1) {
+                next.set(age, cell.get(age) + 1);
+                if *cell.get(age) + 1 >= 2 {
+                    next.set(alive, State::new(0));
+                }
+            }
+        });
+        ca.step();
+        assert_eq!(ca.layers().get(age).cells(), &[1, 0, 2]);
+        assert_eq!(ca.layers().get(alive).cells(), &[State::new(1), State::new(0), State::new(0)]);
+        ca.step();
+        assert_eq!(ca.layers().get(age).cells(), &[2, 0, 2]);
+        assert_eq!(ca.layers().get(alive).cells(), &[State::new(0); 3]);
+        assert_eq!(ca.generation(), 2);
+    }
+}
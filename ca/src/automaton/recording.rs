@@ -0,0 +1,338 @@
+//! Recordings of runs as a [`Snapshot`] of the first generation and the cells
+//! changed by every step, which can be replayed in either direction without
+//! running the rule again
+//!
+//! Encoded recordings are the magic bytes `CARECD`, the format version as a
+//! little endian `u16`, the `u32` length of the encoded snapshot and the
+//! snapshot, the number of steps and then for every step the number of cells
+//! changed followed by `(gap, state)` pairs, ending with a CRC-32 of
+//! everything before it. The gap is the distance in row order from the
+//! previous changed cell, or from the first cell for the first one. Counts
+//! and gaps are unsigned LEB128 integers and states are single bytes.
+
+use crate::automaton::snapshot::{Reader, Snapshot};
+use crate::automaton::Automaton2D;
+use crate::errors::CaError;
+use crate::export::zlib::crc32;
+use crate::grid::dense_grid::DenseGrid;
+use crate::rule::Rule;
+use crate::state::State;
+
+const MAGIC: &[u8; 6] = b"CARECD";
+/// Version written by [`Recording::encode`], the only one that can be read
+pub const VERSION: u16 = 1;
+/// Steps between the grids a [`Replay`] keeps to seek from
+const KEYFRAME_INTERVAL: usize = 64;
+
+/// Cells changed by a step, as row order indices and their new states
+type Delta = Vec<(usize, State)>;
+
+/// A run of an automaton, recorded one step at a time
+///
+/// # Examples
+///
+/// ```
+/// use ca::automaton::recording::Recording;
+/// use ca::automaton::Automaton2D;
+/// use ca::grid::point::Point;
+/// use ca::rule::Rules;
+/// use ca::state::State;
+///
+/// // every cell takes the state of its western neighbour
+/// let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+/// rules.insert(State::new(0), |n| n[0]);
+/// rules.insert(State::new(1), |n| n[0]);
+/// rules.set_neighbourhood(vec![Point::new(-1, 0)]);
+/// let mut automaton = Automaton2D::new(4, 1, rules);
+/// automaton.set_seed(&[(Point::new(0, 0), State::new(1))]).unwrap();
+///
+/// let mut recording = Recording::new(&automaton, "shift east");
+/// recording.run(&mut automaton, 2).unwrap();
+/// let decoded = Recording::decode(&recording.encode()).unwrap();
+/// let mut replay = decoded.replay();
+/// assert_eq!(replay.seek(2).unwrap().get(Point::new(2, 0)), Some(&State::new(1)));
+/// assert_eq!(replay.step_back().unwrap().get(Point::new(1, 0)), Some(&State::new(1)));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    start: Snapshot,
+    deltas: Vec<Delta>,
+    /// Cells of the last recorded generation
+    last: Vec<State>,
+}
+
+impl Recording {
+    /// Start recording from the current generation of `automaton`, `rule`
+    /// describes its rule as in [`Snapshot::capture`]
+    pub fn new<R: Rule>(automaton: &Automaton2D<R>, rule: impl Into<String>) -> Recording {
+        let start = Snapshot::capture(automaton, rule);
+        let last = start.cells().to_vec();
+        Recording { start, deltas: Vec::new(), last }
+    }
+
+    /// Snapshot of the first generation
+    pub fn start(&self) -> &Snapshot {
+        &self.start
+    }
+
+    /// Generation of the first recorded grid
+    pub fn first_generation(&self) -> u64 {
+        self.start.generation()
+    }
+
+    /// Generation of the last recorded grid
+    pub fn last_generation(&self) -> u64 {
+        self.start.generation() + self.deltas.len() as u64
+    }
+
+    /// Number of steps recorded
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Record the step `automaton` has just taken. Fails if it isn't one
+    /// generation after the last one recorded or its grid is another size
+    pub fn record<R: Rule>(&mut self, automaton: &Automaton2D<R>) -> Result<(), CaError> {
+        let grid = automaton.grid();
+        if (grid.width(), grid.height()) != (self.start.width(), self.start.height()) {
+            return Err(CaError::InvalidSnapshot(format!(
+                "a {}x{} grid can't be recorded with a {}x{} one",
+                grid.width(),
+                grid.height(),
+                self.start.width(),
+                self.start.height()
+            )));
+        }
+        if automaton.generation() != self.last_generation() + 1 {
+            return Err(CaError::InvalidSnapshot(format!(
+                "generation {} doesn't follow the last recorded generation {}",
+                automaton.generation(),
+                self.last_generation()
+            )));
+        }
+        let mut delta = Vec::new();
+        for (i, (old, new)) in self.last.iter_mut().zip(grid.cells()).enumerate() {
+            if old != new {
+                delta.push((i, *new));
+                *old = *new;
+            }
+        }
+        self.deltas.push(delta);
+        Ok(())
+    }
+
+    /// Step `automaton` `steps` times, recording every step
+    pub fn run<R: Rule>(&mut self, automaton: &mut Automaton2D<R>, steps: usize) -> Result<(), CaError> {
+        for _ in 0..steps {
+            automaton.step();
+            self.record(automaton)?;
+        }
+        Ok(())
+    }
+
+    /// Play the recording back from its first generation
+    pub fn replay(&self) -> Replay<'_> {
+        Replay::new(self)
+    }
+
+    /// Encode the recording in the binary format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend(VERSION.to_le_bytes());
+        let start = self.start.encode();
+        out.extend((start.len() as u32).to_le_bytes());
+        out.extend(start);
+        write_varint(&mut out, self.deltas.len() as u64);
+        for delta in self.deltas.iter() {
+            write_varint(&mut out, delta.len() as u64);
+            let mut previous = 0;
+            for (i, state) in delta {
+                write_varint(&mut out, (i - previous) as u64);
+                out.push(state.id());
+                previous = *i;
+            }
+        }
+        let crc = crc32(&out);
+        out.extend(crc.to_le_bytes());
+        out
+    }
+
+    /// Decode a recording, checking its checksum and version. Further steps
+    /// can be recorded onto it
+    pub fn decode(bytes: &[u8]) -> Result<Recording, CaError> {
+        let err = |reason: &str| CaError::InvalidSnapshot(reason.to_string());
+        if bytes.len() < MAGIC.len() + 2 + 4 || !bytes.starts_with(MAGIC) {
+            return Err(err("not a recording"));
+        }
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if crc32(body).to_le_bytes() != crc {
+            return Err(err("checksum doesn't match"));
+        }
+        let mut r = Reader::new(body, MAGIC.len());
+        let version = r.u16()?;
+        if version != VERSION {
+            return Err(CaError::InvalidSnapshot(format!("version {} can't be read, only {}", version, VERSION)));
+        }
+
+        let len = r.u32()? as usize;
+        let start = Snapshot::decode(r.bytes(len)?)?;
+        let mut last = start.cells().to_vec();
+        let steps = r.varint()?;
+        let mut deltas = Vec::new();
+        for _ in 0..steps {
+            let mut delta = Vec::new();
+            let mut i = 0usize;
+            for _ in 0..r.varint()? {
+                i = i.checked_add(r.varint()? as usize).ok_or_else(|| err("a cell is outside of the grid"))?;
+                let state = State::new(r.u8()?);
+                *last.get_mut(i).ok_or_else(|| err("a cell is outside of the grid"))? = state;
+                delta.push((i, state));
+            }
+            deltas.push(delta);
+        }
+        if !r.is_at_end() {
+            return Err(err("there are bytes after the steps"));
+        }
+        Ok(Recording { start, deltas, last })
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Grid of a [`Recording`] at any of its generations. Every
+/// `KEYFRAME_INTERVAL` steps the grid is kept, so seeking replays at most that
+/// many steps
+pub struct Replay<'a> {
+    recording: &'a Recording,
+    grid: DenseGrid<State>,
+    /// Steps since the first generation
+    position: usize,
+    keyframes: Vec<Vec<State>>,
+}
+
+impl<'a> Replay<'a> {
+    fn new(recording: &'a Recording) -> Replay<'a> {
+        let grid = recording.start.grid();
+        let mut cells = grid.cells().to_vec();
+        let mut keyframes = vec![cells.clone()];
+        for (step, delta) in recording.deltas.iter().enumerate() {
+            for (i, state) in delta {
+                cells[*i] = *state;
+            }
+            if (step + 1) % KEYFRAME_INTERVAL == 0 {
+                keyframes.push(cells.clone());
+            }
+        }
+        Replay { recording, grid, position: 0, keyframes }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.recording.first_generation() + self.position as u64
+    }
+
+    pub fn grid(&self) -> &DenseGrid<State> {
+        &self.grid
+    }
+
+    /// Move to `generation`, `None` if it wasn't recorded
+    pub fn seek(&mut self, generation: u64) -> Option<&DenseGrid<State>> {
+        let target = generation.checked_sub(self.recording.first_generation())? as usize;
+        if target > self.recording.deltas.len() {
+            return None;
+        }
+        let keyframe = target / KEYFRAME_INTERVAL;
+        if target < self.position || target - self.position > target - keyframe * KEYFRAME_INTERVAL {
+            self.grid.cells_mut().copy_from_slice(&self.keyframes[keyframe]);
+            self.position = keyframe * KEYFRAME_INTERVAL;
+        }
+        for delta in &self.recording.deltas[self.position..target] {
+            for (i, state) in delta {
+                self.grid.cells_mut()[*i] = *state;
+            }
+        }
+        self.position = target;
+        Some(&self.grid)
+    }
+
+    /// Move to the next generation, `None` at the end of the recording
+    pub fn step_forward(&mut self) -> Option<&DenseGrid<State>> {
+        self.seek(self.generation() + 1)
+    }
+
+    /// Move to the previous generation, `None` at the start of the recording
+    pub fn step_back(&mut self) -> Option<&DenseGrid<State>> {
+        self.seek(self.generation().checked_sub(1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+    use crate::rng::Rng;
+
+    /// Every cell flips with probability one tenth
+    struct Flip;
+
+    impl Rule for Flip {
+        fn neighbourhood(&self) -> &[Point<i32>] {
+            &[]
+        }
+
+        fn apply(&self, state: State, _neighbours: &[State], _point: Point<i32>, _generation: u64, rng: &mut Rng) -> State {
+            if rng.chance(0.1) {
+                State::new(1 - state.id())
+            } else {
+                state
+            }
+        }
+    }
+
+    #[test]
+    fn replay_test() {
+        let mut automaton = Automaton2D::new(40, 30, Flip);
+        automaton.set_rng_seed(7);
+        automaton.run(5);
+        let mut recording = Recording::new(&automaton, "flip");
+        let mut grids = vec![automaton.grid().clone()];
+        for _ in 0..150 {
+            automaton.step();
+            recording.record(&automaton).unwrap();
+            grids.push(automaton.grid().clone());
+        }
+        assert_eq!((recording.first_generation(), recording.last_generation()), (5, 155));
+
+        // each step changes about a tenth of the cells
+        let encoded = recording.encode();
+        assert!(encoded.len() < 150 * 40 * 30 / 4);
+        let decoded = Recording::decode(&encoded).unwrap();
+        assert_eq!(decoded, recording);
+
+        let mut replay = decoded.replay();
+        for generation in [100, 3, 155, 64 + 5, 20, 21, 130, 5] {
+            let grid = replay.seek(generation).cloned();
+            assert_eq!(grid.as_ref(), grids.get((generation as usize).wrapping_sub(5)), "{}", generation);
+        }
+        assert_eq!(replay.generation(), 5);
+        assert!(replay.step_back().is_none());
+        assert_eq!(replay.step_forward(), Some(&grids[1]));
+        assert!(replay.seek(156).is_none());
+
+        // a generation was skipped
+        automaton.run(2);
+        assert!(recording.record(&automaton).is_err());
+        let mut corrupt = encoded.clone();
+        corrupt[40] ^= 1;
+        assert!(matches!(Recording::decode(&corrupt), Err(CaError::InvalidSnapshot(_))));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::grid::point::Point;
+use crate::rng::Rng;
+
+/// Order cells are updated in during a step of an
+/// [`Automaton2D`](crate::automaton::Automaton2D)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Schedule {
+    /// Every cell at once from the states of the previous step
+    #[default]
+    Synchronous,
+    /// One cell at a time row by row, each seeing the cells updated before it
+    Sweep,
+    /// One cell at a time in a new random order every step
+    RandomSequential,
+    /// Every cell at once, but each only updates with the given probability
+    RandomIndependent(f64),
+    /// Every cell has a clock with a random period from 1 to `max_period`
+    /// and a random phase, and updates with the others whose clock ticks
+    Clocked { max_period: u64 },
+}
+
+impl Schedule {
+    /// Whether cells are updated one at a time, seeing each other's new states
+    pub fn is_sequential(&self) -> bool {
+        matches!(self, Schedule::Sweep | Schedule::RandomSequential)
+    }
+
+    /// Whether the cell at `point` updates in `generation`, for schedules
+    /// updating cells at once
+    pub(crate) fn updates(&self, seed: u64, point: Point<i32>, generation: u64) -> bool {
+        match *self {
+            Schedule::RandomIndependent(p) => Rng::for_cell(!seed, point, generation).chance(p),
+            Schedule::Clocked { max_period } => {
+                let mut rng = Rng::for_cell(!seed, point, 0);
+                let period = 1 + rng.below(max_period.max(1));
+                let phase = rng.below(period);
+                (generation + phase).is_multiple_of(period)
+            }
+            _ => true,
+        }
+    }
+
+    /// Indices of the `n` cells in the order they update in `generation`, for
+    /// sequential schedules
+    pub(crate) fn order(&self, seed: u64, generation: u64, n: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..n).collect();
+        if *self == Schedule::RandomSequential {
+            let mut rng = Rng::for_step(seed, generation);
+            for i in (1..n).rev() {
+                order.swap(i, rng.below(i as u64 + 1) as usize);
+            }
+        }
+        order
+    }
+}
@@ -0,0 +1,491 @@
+//! Checkpoints of an [`Automaton2D`] in a versioned binary format
+//!
+//! All integers are little endian. A snapshot is the magic bytes `CASNAP`,
+//! the format version as a `u16`, the body and a CRC-32 of everything before
+//! it. Strings are a `u32` length followed by UTF-8 and the cells are
+//! `(u32 count, u8 state)` runs in row order. Rules can hold arbitrary code so
+//! only a description of them is stored, and the rule is passed back in when
+//! restoring. Cells draw random numbers from streams derived from the seed
+//! and the generation, so those two are the whole random state.
+
+use std::io::{Read, Write};
+
+use crate::automaton::schedule::Schedule;
+use crate::automaton::Automaton2D;
+use crate::errors::CaError;
+use crate::export::zlib::crc32;
+use crate::grid::dense_grid::{Boundary, DenseGrid};
+use crate::grid::point::{IsPoint, Point};
+use crate::rule::Rule;
+use crate::state::{Rgb, State, StateSet};
+
+const MAGIC: &[u8; 6] = b"CASNAP";
+/// Version written by [`Snapshot::encode`], the only one that can be read
+pub const VERSION: u16 = 1;
+
+/// Everything needed to resume an [`Automaton2D`] apart from its rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    width: usize,
+    height: usize,
+    boundary: Boundary,
+    schedule: Schedule,
+    neighbourhood: Vec<Point<i32>>,
+    states: StateSet,
+    rule: String,
+    generation: u64,
+    rng_seed: u64,
+    cells: Vec<State>,
+}
+
+impl Snapshot {
+    /// Capture the current state of `automaton`, `rule` describes its rule,
+    /// e.g. the source it was compiled from
+    pub fn capture<R: Rule>(automaton: &Automaton2D<R>, rule: impl Into<String>) -> Snapshot {
+        Snapshot {
+            width: automaton.grid.width(),
+            height: automaton.grid.height(),
+            boundary: automaton.grid.boundary(),
+            schedule: automaton.schedule,
+            neighbourhood: automaton.rules.neighbourhood().to_vec(),
+            states: automaton.states.clone(),
+            rule: rule.into(),
+            generation: automaton.generation,
+            rng_seed: automaton.rng_seed,
+            cells: automaton.grid.cells().to_vec(),
+        }
+    }
+
+    /// Rebuild the automaton with `rules`, which must have the neighbourhood
+    /// it was captured with
+    pub fn restore<R: Rule>(&self, rules: R) -> Result<Automaton2D<R>, CaError> {
+        if rules.neighbourhood() != self.neighbourhood.as_slice() {
+            return Err(CaError::InvalidNeighbourhood(format!(
+                "the rule's neighbourhood {:?} isn't the snapshot's {:?}",
+                rules.neighbourhood(),
+                self.neighbourhood
+            )));
+        }
+        let mut automaton = Automaton2D::new(self.width, self.height, rules);
+        automaton.grid = self.grid();
+        automaton.states = self.states.clone();
+        automaton.schedule = self.schedule;
+        automaton.generation = self.generation;
+        automaton.rng_seed = self.rng_seed;
+        Ok(automaton)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    pub fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    pub fn states(&self) -> &StateSet {
+        &self.states
+    }
+
+    /// Description of the rule given when capturing
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    /// Cells in row order
+    pub fn cells(&self) -> &[State] {
+        &self.cells
+    }
+
+    /// Grid of the cells with the captured boundary
+    pub fn grid(&self) -> DenseGrid<State> {
+        let mut grid = DenseGrid::with_boundary(self.width, self.height, self.boundary);
+        grid.cells_mut().copy_from_slice(&self.cells);
+        grid
+    }
+
+    /// Encode the snapshot in the binary format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend(VERSION.to_le_bytes());
+        out.extend((self.width as u32).to_le_bytes());
+        out.extend((self.height as u32).to_le_bytes());
+        out.push(match self.boundary {
+            Boundary::Fixed => 0,
+            Boundary::Toroidal => 1,
+        });
+        match self.schedule {
+            Schedule::Synchronous => out.push(0),
+            Schedule::Sweep => out.push(1),
+            Schedule::RandomSequential => out.push(2),
+            Schedule::RandomIndependent(p) => {
+                out.push(3);
+                out.extend(p.to_le_bytes());
+            }
+            Schedule::Clocked { max_period } => {
+                out.push(4);
+                out.extend(max_period.to_le_bytes());
+            }
+        }
+
+        out.extend((self.neighbourhood.len() as u32).to_le_bytes());
+        for p in self.neighbourhood.iter() {
+            out.extend(p.x().to_le_bytes());
+            out.extend(p.y().to_le_bytes());
+        }
+
+        out.extend((self.states.len() as u32).to_le_bytes());
+        for (state, name) in self.states.iter() {
+            out.push(state.id());
+            write_str(&mut out, name);
+            let info = self.states.info(state).cloned().unwrap_or_default();
+            match info.colour() {
+                Some(c) => out.extend([1, c.0, c.1, c.2]),
+                None => out.push(0),
+            }
+            match info.glyph() {
+                Some(g) => {
+                    out.push(1);
+                    out.extend((g as u32).to_le_bytes());
+                }
+                None => out.push(0),
+            }
+            match info.description() {
+                Some(d) => {
+                    out.push(1);
+                    write_str(&mut out, d);
+                }
+                None => out.push(0),
+            }
+        }
+
+        write_str(&mut out, &self.rule);
+        out.extend(self.generation.to_le_bytes());
+        out.extend(self.rng_seed.to_le_bytes());
+        let runs: Vec<_> = self.cells.chunk_by(|a, b| a == b).collect();
+        out.extend((runs.len() as u32).to_le_bytes());
+        for run in runs {
+            out.extend((run.len() as u32).to_le_bytes());
+            out.push(run[0].id());
+        }
+
+        let crc = crc32(&out);
+        out.extend(crc.to_le_bytes());
+        out
+    }
+
+    /// Decode a snapshot, checking its checksum and version
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::automaton::snapshot::Snapshot;
+    /// use ca::automaton::Automaton2D;
+    /// use ca::grid::point::Point;
+    /// use ca::rule::Rules;
+    /// use ca::state::State;
+    ///
+    /// type Empty = Rules<State, fn(&[State]) -> State>;
+    /// let mut automaton = Automaton2D::new(4, 4, Empty::new());
+    /// automaton.set_seed(&[(Point::new(1, 2), State::new(1))]).unwrap();
+    /// let bytes = Snapshot::capture(&automaton, "empty").encode();
+    ///
+    /// let snapshot = Snapshot::decode(&bytes).unwrap();
+    /// let restored = snapshot.restore(Empty::new()).unwrap();
+    /// assert_eq!(restored.grid(), automaton.grid());
+    /// assert_eq!(snapshot.rule(), "empty");
+    /// ```
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, CaError> {
+        let err = |reason: &str| CaError::InvalidSnapshot(reason.to_string());
+        if bytes.len() < MAGIC.len() + 2 + 4 || !bytes.starts_with(MAGIC) {
+            return Err(err("not a snapshot"));
+        }
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if crc32(body).to_le_bytes() != crc {
+            return Err(err("checksum doesn't match"));
+        }
+        let mut r = Reader::new(body, MAGIC.len());
+        let version = r.u16()?;
+        if version != VERSION {
+            return Err(CaError::InvalidSnapshot(format!("version {} can't be read, only {}", version, VERSION)));
+        }
+
+        let width = r.u32()? as usize;
+        let height = r.u32()? as usize;
+        let boundary = match r.u8()? {
+            0 => Boundary::Fixed,
+            1 => Boundary::Toroidal,
+            _ => return Err(err("unknown boundary")),
+        };
+        let schedule = match r.u8()? {
+            0 => Schedule::Synchronous,
+            1 => Schedule::Sweep,
+            2 => Schedule::RandomSequential,
+            3 => Schedule::RandomIndependent(f64::from_bits(r.u64()?)),
+            4 => Schedule::Clocked { max_period: r.u64()? },
+            _ => return Err(err("unknown schedule")),
+        };
+
+        let mut neighbourhood = Vec::new();
+        for _ in 0..r.u32()? {
+            let x = r.u32()? as i32;
+            neighbourhood.push(Point::new(x, r.u32()? as i32));
+        }
+
+        let mut states = StateSet::new();
+        for _ in 0..r.u32()? {
+            let state = State::new(r.u8()?);
+            if !states.insert(r.str()?, state) {
+                return Err(err("a state is repeated"));
+            }
+            let info = states.info_mut(state).expect("state was just inserted");
+            if r.u8()? != 0 {
+                info.set_colour(Some(Rgb(r.u8()?, r.u8()?, r.u8()?)));
+            }
+            if r.u8()? != 0 {
+                info.set_glyph(Some(char::from_u32(r.u32()?).ok_or_else(|| err("invalid glyph"))?));
+            }
+            if r.u8()? != 0 {
+                info.set_description(Some(r.str()?));
+            }
+        }
+
+        let rule = r.str()?;
+        let generation = r.u64()?;
+        let rng_seed = r.u64()?;
+        let mut cells = Vec::new();
+        for _ in 0..r.u32()? {
+            let count = r.u32()? as usize;
+            let state = State::new(r.u8()?);
+            if cells.len() + count > width * height {
+                return Err(err("there are more cells than fit in the grid"));
+            }
+            cells.extend(std::iter::repeat_n(state, count));
+        }
+        if cells.len() != width * height {
+            return Err(err("there are fewer cells than fit in the grid"));
+        }
+        if !r.is_at_end() {
+            return Err(err("there are bytes after the cells"));
+        }
+
+        Ok(Snapshot { width, height, boundary, schedule, neighbourhood, states, rule, generation, rng_seed, cells })
+    }
+
+    /// Write the encoded snapshot to `writer`
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), CaError> {
+        writer.write_all(&self.encode())?;
+        Ok(())
+    }
+
+    /// Read an encoded snapshot until the end of `reader`
+    pub fn read(reader: &mut impl Read) -> Result<Snapshot, CaError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Snapshot::decode(&bytes)
+    }
+}
+
+impl<R> Automaton2D<R>
+where
+    R: Rule, {
+
+    /// Save a checkpoint of the automaton to `writer`, see [`Snapshot`]
+    pub fn save(&self, rule: impl Into<String>, writer: &mut impl Write) -> Result<(), CaError> {
+        Snapshot::capture(self, rule).write(writer)
+    }
+
+    /// Resume an automaton saved with [`Automaton2D::save`], running `rules`
+    pub fn load(reader: &mut impl Read, rules: R) -> Result<Automaton2D<R>, CaError> {
+        Snapshot::read(reader)?.restore(rules)
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    out.extend((s.len() as u32).to_le_bytes());
+    out.extend(s.as_bytes());
+}
+
+/// Reads the fields of a snapshot body in order
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Read `bytes` from `pos` on
+    pub fn new(bytes: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { bytes, pos }
+    }
+
+    /// Whether every byte has been read
+    pub fn is_at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    /// The next `len` bytes
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], CaError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| CaError::InvalidSnapshot("it ends early".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn take<const N: usize>(&mut self) -> Result<[u8; N], CaError> {
+        Ok(self.bytes(N)?.try_into().expect("slice has N bytes"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, CaError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, CaError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, CaError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, CaError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn str(&mut self) -> Result<String, CaError> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| CaError::InvalidSnapshot("a string isn't UTF-8".to_string()))
+    }
+
+    /// Unsigned LEB128 integer of at most 64 bits
+    pub fn varint(&mut self) -> Result<u64, CaError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            // the tenth byte only has room for the top bit
+            if shift == 63 && byte > 1 {
+                break;
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CaError::InvalidSnapshot("an integer is too long".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    /// Every cell becomes state 1 with probability one half
+    struct Coin(Vec<Point<i32>>);
+
+    impl Rule for Coin {
+        fn neighbourhood(&self) -> &[Point<i32>] {
+            &self.0
+        }
+
+        fn apply(&self, _state: State, _neighbours: &[State], _point: Point<i32>, _generation: u64, rng: &mut Rng) -> State {
+            State::new(rng.chance(0.5) as u8)
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trip_test() {
+        let neighbourhood = vec![Point::new(-1, 0), Point::new(1, -1)];
+        let mut automaton = Automaton2D::new(7, 5, Coin(neighbourhood.clone()));
+        let mut states = StateSet::new();
+        states.push("tails");
+        let heads = states.push("heads").unwrap();
+        let info = states.info_mut(heads).unwrap();
+        info.set_colour(Some(Rgb(1, 2, 3)));
+        info.set_glyph(Some('é'));
+        info.set_description(Some("a coin showing heads".to_string()));
+        automaton.set_states(states);
+        automaton.set_boundary(Boundary::Toroidal);
+        automaton.set_schedule(Schedule::RandomIndependent(0.25));
+        automaton.set_rng_seed(99);
+        automaton.run(3);
+
+        let mut file = Vec::new();
+        automaton.save("coin", &mut file).unwrap();
+        let mut restored = Automaton2D::load(&mut file.as_slice(), Coin(neighbourhood.clone())).unwrap();
+        assert_eq!(restored.grid(), automaton.grid());
+        assert_eq!(restored.states(), automaton.states());
+        assert_eq!(restored.schedule(), automaton.schedule());
+        assert_eq!(restored.generation(), 3);
+        assert_eq!(Snapshot::decode(&file).unwrap().rule(), "coin");
+
+        // the restored run carries on exactly as the original
+        automaton.run(4);
+        restored.run(4);
+        assert_eq!(restored.grid(), automaton.grid());
+
+        let wrong = Snapshot::decode(&file).unwrap().restore(Coin(vec![]));
+        assert!(matches!(wrong, Err(CaError::InvalidNeighbourhood(_))));
+    }
+
+    #[test]
+    fn varint_test() {
+        let read = |bytes: &[u8]| Reader::new(bytes, 0).varint();
+        assert_eq!(read(&[0x7f]).unwrap(), 127);
+        assert_eq!(read(&[0xe5, 0x8e, 0x26]).unwrap(), 624_485);
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(read(&max).unwrap(), u64::MAX);
+        // 65 bits, and a continuation past 64 bits
+        assert!(read(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x03]).is_err());
+        assert!(read(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x81, 0x00]).is_err());
+        assert!(read(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn snapshot_errors_test() {
+        let automaton = Automaton2D::new(3, 3, Coin(vec![]));
+        let bytes = Snapshot::capture(&automaton, "").encode();
+        let error = |bytes: &[u8]| match Snapshot::decode(bytes) {
+            Err(CaError::InvalidSnapshot(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(error(b"PNG"), "not a snapshot");
+        let mut corrupt = bytes.clone();
+        corrupt[10] ^= 1;
+        assert_eq!(error(&corrupt), "checksum doesn't match");
+
+        let mut newer = bytes[..bytes.len() - 4].to_vec();
+        newer[6] = 2;
+        let crc = crc32(&newer);
+        newer.extend(crc.to_le_bytes());
+        assert_eq!(error(&newer), "version 2 can't be read, only 1");
+
+        let mut short = bytes[..bytes.len() - 9].to_vec();
+        let crc = crc32(&short);
+        short.extend(crc.to_le_bytes());
+        assert_eq!(error(&short), "it ends early");
+    }
+}
@@ -0,0 +1,214 @@
+//! Experiments described entirely by a configuration file. With the `serde`
+//! feature a [`Config`] can be read from JSON, TOML or any other serde format,
+//! fields other than the size and rule are optional
+//!
+//! ```toml
+//! width = 64
+//! height = 64
+//! boundary = "toroidal"
+//! neighbourhood = "chebyshev_distance"
+//! radius = 2
+//! rule = """
+//! states: dead = 0, alive = 1
+//! neighbourhood: moore
+//! transitions: dead -> alive when count(alive) in 4..=6
+//! """
+//! seed = "patterns/seed.rle"
+//! steps = 100
+//! ```
+
+use std::path::PathBuf;
+
+use crate::automaton::schedule::Schedule;
+use crate::automaton::Automaton2D;
+use crate::errors::CaError;
+use crate::grid::dense_grid::Boundary;
+use crate::grid::neighbourhood::{relative_neighbourhood_coords, NType};
+use crate::grid::point::Point;
+use crate::parse::{parse_ca_string, NeighbourhoodDecl, ParsedCA};
+use crate::pattern;
+use crate::rule::DynRules;
+
+/// An automaton, its starting pattern and how long to run it for
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Config {
+    pub width: usize,
+    pub height: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub boundary: Boundary,
+    /// Replaces the type of the rule's default neighbourhood
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub neighbourhood: Option<NType>,
+    /// Replaces the size of the rule's default neighbourhood, only distance
+    /// neighbourhoods have one and it's at most
+    /// [`MAX_DISTANCE`](crate::grid::neighbourhood::MAX_DISTANCE)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub radius: Option<i32>,
+    /// Rule in the [CA description language](crate::parse)
+    pub rule: String,
+    /// Pattern file the grid starts with, see [`pattern::load`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub seed: Option<PathBuf>,
+    /// Where the top left corner of the seed is placed, centred by default
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub seed_origin: Option<Point<i32>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub steps: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rng_seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub schedule: Schedule,
+}
+
+impl Config {
+    /// Return a new `Config` of an empty grid which isn't run, with every
+    /// other field at its default
+    pub fn new(width: usize, height: usize, rule: impl Into<String>) -> Config {
+        Config {
+            width,
+            height,
+            boundary: Boundary::default(),
+            neighbourhood: None,
+            radius: None,
+            rule: rule.into(),
+            seed: None,
+            seed_origin: None,
+            steps: 0,
+            rng_seed: 0,
+            schedule: Schedule::default(),
+        }
+    }
+
+    /// Parse the rule, with the configured neighbourhood in place of its
+    /// default one
+    pub fn parsed(&self) -> Result<ParsedCA, CaError> {
+        let mut pca = parse_ca_string(&self.rule)?;
+        if self.neighbourhood.is_none() && self.radius.is_none() {
+            return Ok(pca);
+        }
+        let mut decls = pca.neighbourhoods().to_vec();
+        let n_type = self
+            .neighbourhood
+            .or(decls.first().map(|d| d.n_type()))
+            .ok_or_else(|| CaError::InvalidNeighbourhood("a radius needs a neighbourhood type".to_string()))?;
+        if let (Some(radius), NType::Moore | NType::VonNeumann | NType::Hexagonal) = (self.radius, n_type) {
+            return Err(CaError::InvalidNeighbourhood(format!("{:?} doesn't take a radius, got {}", n_type, radius)));
+        }
+        relative_neighbourhood_coords(n_type, self.radius)?;
+        let decl = NeighbourhoodDecl::new(decls.first().and_then(|d| d.name().map(String::from)), n_type, self.radius);
+        match decls.first_mut() {
+            Some(first) => *first = decl,
+            None => decls.push(decl),
+        }
+        pca.set_neighbourhoods(decls);
+        Ok(pca)
+    }
+
+    /// Build the automaton at generation 0 with the seed loaded
+    pub fn automaton(&self) -> Result<Automaton2D<DynRules>, CaError> {
+        let mut automaton = self.parsed()?.automaton(self.width, self.height)?;
+        automaton.set_boundary(self.boundary);
+        automaton.set_schedule(self.schedule);
+        automaton.set_rng_seed(self.rng_seed);
+        if let Some(path) = &self.seed {
+            let seed = pattern::load(path)?;
+            let origin = self.seed_origin.unwrap_or(Point::new(
+                (self.width as i32 - seed.width() as i32) / 2,
+                (self.height as i32 - seed.height() as i32) / 2,
+            ));
+            automaton.set_seed(&seed.cells_at(origin))?;
+        }
+        Ok(automaton)
+    }
+
+    /// Build the automaton and run it for the configured number of steps
+    pub fn run(&self) -> Result<Automaton2D<DynRules>, CaError> {
+        let mut automaton = self.automaton()?;
+        automaton.run(self.steps);
+        Ok(automaton)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+    use crate::state::State;
+
+    const LIFE: &str = "
+        states: dead = 0, alive = 1
+        neighbourhood: moore
+        transitions:
+            dead -> alive when count(alive) == 3
+            alive -> dead when count(alive) < 2 or count(alive) > 3
+    ";
+
+    #[test]
+    fn config_run_test() {
+        let path = std::env::temp_dir().join(format!("ca_config_{}.rle", std::process::id()));
+        std::fs::write(&path, "x = 3, y = 1\n3o!").unwrap();
+        let mut config = Config::new(5, 5, LIFE);
+        config.seed = Some(path.clone());
+        config.steps = 1;
+        let automaton = config.run();
+        std::fs::remove_file(&path).unwrap();
+
+        // a blinker centred in the grid turns vertical
+        let automaton = automaton.unwrap();
+        let alive: Vec<_> = automaton.grid().iter().filter(|(_, s)| **s == State::new(1)).map(|(p, _)| p).collect();
+        assert_eq!(alive, [Point::new(2, 1), Point::new(2, 2), Point::new(2, 3)]);
+        assert_eq!(automaton.generation(), 1);
+
+        config.seed = None;
+        config.neighbourhood = Some(NType::ChebyshevDistance);
+        config.radius = Some(2);
+        assert_eq!(config.automaton().unwrap().rules().neighbourhood().len(), 24);
+        config.radius = None;
+        assert!(matches!(config.automaton(), Err(CaError::InvalidNeighbourhood(_))));
+        // the same limit as radii in rules
+        config.radius = Some(100_000);
+        assert!(matches!(config.automaton(), Err(CaError::InvalidNeighbourhood(_))));
+        config.neighbourhood = Some(NType::ManhattanDistance);
+        config.radius = Some(65);
+        assert!(matches!(config.automaton(), Err(CaError::InvalidNeighbourhood(_))));
+
+        // Moore is always radius 1, a radius isn't silently dropped
+        config.neighbourhood = Some(NType::Moore);
+        config.radius = Some(3);
+        assert!(matches!(config.automaton(), Err(CaError::InvalidNeighbourhood(_))));
+        config.neighbourhood = None;
+        assert!(matches!(config.automaton(), Err(CaError::InvalidNeighbourhood(_))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_serde_test() {
+        let json = r##"{
+            "width": 8,
+            "height": 4,
+            "boundary": "toroidal",
+            "neighbourhood": "von_neumann",
+            "rule": "states: off = 0, on = 1 (colour = \"#ff0000\")\nneighbourhood: moore\ntransitions: off -> on when count(on) > 0",
+            "seed_origin": {"x": 1, "y": 2},
+            "schedule": {"random_independent": 0.5}
+        }"##;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(config.boundary, Boundary::Toroidal);
+        assert_eq!(config.seed_origin, Some(Point::new(1, 2)));
+        assert_eq!(config.schedule, Schedule::RandomIndependent(0.5));
+        assert_eq!(config.steps, 0);
+        assert_eq!(config.automaton().unwrap().rules().neighbourhood().len(), 4);
+
+        let toml = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+
+        // parsed descriptions round trip too
+        let pca = config.parsed().unwrap();
+        let json = serde_json::to_string(&pca).unwrap();
+        let back: ParsedCA = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, pca);
+        assert_eq!(back.states().info(State::new(1)).unwrap().colour(), Some(crate::state::Rgb(255, 0, 0)));
+        assert!(serde_json::from_str::<ParsedCA>(&json.replace("\"on\"", "\"off\"")).is_err());
+    }
+}
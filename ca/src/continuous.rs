@@ -0,0 +1,340 @@
+//! Automata whose cells hold a continuous value in `[0, 1]`, updated from the
+//! convolution of the grid with a [`Kernel`](kernel::Kernel), such as Lenia
+//! and SmoothLife
+
+pub mod fft;
+pub mod kernel;
+
+use crate::grid::dense_grid::{Boundary, DenseGrid};
+use kernel::{Convolution, Convolver, Kernel};
+use std::fmt::Debug;
+
+/// Floating point type a continuous cell is stored as. Arithmetic is done in
+/// `f64` whatever the storage
+pub trait Real: Copy + Clone + Default + Debug + PartialOrd + Send + Sync + 'static {
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Real for f32 {
+    fn from_f64(v: f64) -> f32 {
+        v as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    fn from_f64(v: f64) -> f64 {
+        v
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Growth of a cell given the convolution of its neighbourhood, from -1 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Growth {
+    /// Smooth peak at `mu` of width `sigma`, as used by Lenia
+    Gaussian { mu: f64, sigma: f64 },
+    /// Quartic bump reaching -1 at `mu ± 3 sigma`
+    Polynomial { mu: f64, sigma: f64 },
+    /// 1 within `sigma` of `mu`, otherwise -1
+    Step { mu: f64, sigma: f64 },
+}
+
+impl Growth {
+    pub fn apply(&self, u: f64) -> f64 {
+        match *self {
+            Growth::Gaussian { mu, sigma } => {
+                2.0 * (-(u - mu).powi(2) / (2.0 * sigma * sigma)).exp() - 1.0
+            }
+            Growth::Polynomial { mu, sigma } => {
+                let d = (u - mu).powi(2) / (9.0 * sigma * sigma);
+                if d < 1.0 { 2.0 * (1.0 - d).powi(4) - 1.0 } else { -1.0 }
+            }
+            Growth::Step { mu, sigma } => if (u - mu).abs() <= sigma { 1.0 } else { -1.0 },
+        }
+    }
+}
+
+/// Lenia: every step each cell grows by `dt * growth(kernel * grid)`, clipped
+/// to `[0, 1]`
+///
+/// # Examples
+///
+/// ```
+/// use ca::continuous::kernel::Kernel;
+/// use ca::continuous::{Growth, Lenia};
+/// use ca::grid::point::Point;
+///
+/// let growth = Growth::Gaussian { mu: 0.15, sigma: 0.015 };
+/// let mut lenia: Lenia<f64> = Lenia::new(64, 64, Kernel::ring(13, &[1.0]), growth, 0.1);
+/// lenia.grid_mut().set(Point::new(32, 32), 1.0);
+/// // the ring around a lone cell is empty, so it shrinks by dt every step
+/// lenia.run(4);
+/// assert!((lenia.grid().get(Point::new(32, 32)).unwrap() - 0.6).abs() < 1e-9);
+/// lenia.run(6);
+/// assert!(lenia.grid().cells().iter().all(|v| *v < 1e-9));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lenia<T>
+where
+    T: Real, {
+    grid: DenseGrid<T>,
+    kernel: Convolver,
+    growth: Growth,
+    dt: f64,
+    generation: u64,
+}
+
+impl<T> Lenia<T>
+where
+    T: Real, {
+    /// Return a new toroidal `Lenia` with every cell at 0
+    pub fn new(width: usize, height: usize, kernel: Kernel, growth: Growth, dt: f64) -> Lenia<T> {
+        Lenia {
+            grid: DenseGrid::with_boundary(width, height, Boundary::Toroidal),
+            kernel: Convolver::new(kernel, Convolution::Auto),
+            growth,
+            dt,
+            generation: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &DenseGrid<T> {
+        &self.grid
+    }
+
+    pub fn grid_mut(&mut self) -> &mut DenseGrid<T> {
+        &mut self.grid
+    }
+
+    pub fn kernel(&self) -> &Kernel {
+        self.kernel.kernel()
+    }
+
+    pub fn growth(&self) -> Growth {
+        self.growth
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Choose between direct and FFT convolution
+    pub fn set_convolution(&mut self, method: Convolution) {
+        self.kernel.set_method(method);
+    }
+
+    pub fn step(&mut self) {
+        let potential = self.kernel.convolve(&self.grid);
+        for (cell, u) in self.grid.cells_mut().iter_mut().zip(potential) {
+            let v = cell.to_f64() + self.dt * self.growth.apply(u);
+            *cell = T::from_f64(v.clamp(0.0, 1.0));
+        }
+        self.generation += 1;
+    }
+
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+/// Parameters of [`SmoothLife`], following Rafler's paper
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmoothLifeParams {
+    /// Radius of the inner disc, the outer annulus reaches `3 * inner_radius`
+    pub inner_radius: f64,
+    /// Range of the annulus filling a dead cell is born in
+    pub birth: (f64, f64),
+    /// Range of the annulus filling a live cell survives in
+    pub survival: (f64, f64),
+    /// Smoothness of the annulus and disc thresholds
+    pub alpha_n: f64,
+    pub alpha_m: f64,
+    /// Move cells towards their new value by this step rather than replacing it
+    pub dt: Option<f64>,
+}
+
+impl Default for SmoothLifeParams {
+    fn default() -> SmoothLifeParams {
+        SmoothLifeParams {
+            inner_radius: 7.0,
+            birth: (0.278, 0.365),
+            survival: (0.267, 0.445),
+            alpha_n: 0.028,
+            alpha_m: 0.147,
+            dt: None,
+        }
+    }
+}
+
+/// SmoothLife: the filling of a disc around each cell decides how alive it
+/// is, and the filling of the annulus around it whether it is born or
+/// survives
+#[derive(Clone, Debug)]
+pub struct SmoothLife<T>
+where
+    T: Real, {
+    grid: DenseGrid<T>,
+    inner: Convolver,
+    outer: Convolver,
+    params: SmoothLifeParams,
+    generation: u64,
+}
+
+impl<T> SmoothLife<T>
+where
+    T: Real, {
+    /// Return a new toroidal `SmoothLife` with every cell at 0
+    pub fn new(width: usize, height: usize, params: SmoothLifeParams) -> SmoothLife<T> {
+        let r = params.inner_radius;
+        SmoothLife {
+            grid: DenseGrid::with_boundary(width, height, Boundary::Toroidal),
+            inner: Convolver::new(Kernel::disc(r), Convolution::Auto),
+            outer: Convolver::new(Kernel::annulus(r, 3.0 * r), Convolution::Auto),
+            params,
+            generation: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &DenseGrid<T> {
+        &self.grid
+    }
+
+    pub fn grid_mut(&mut self) -> &mut DenseGrid<T> {
+        &mut self.grid
+    }
+
+    pub fn params(&self) -> &SmoothLifeParams {
+        &self.params
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Choose between direct and FFT convolution
+    pub fn set_convolution(&mut self, method: Convolution) {
+        self.inner.set_method(method);
+        self.outer.set_method(method);
+    }
+
+    /// New value of a cell from its disc filling `m` and annulus filling `n`
+    fn transition(&self, n: f64, m: f64) -> f64 {
+        let p = &self.params;
+        let sigma = |x: f64, a: f64, alpha: f64| 1.0 / (1.0 + (-(x - a) * 4.0 / alpha).exp());
+        let alive = sigma(m, 0.5, p.alpha_m);
+        let mix = |dead: f64, live: f64| dead * (1.0 - alive) + live * alive;
+        let (low, high) = (mix(p.birth.0, p.survival.0), mix(p.birth.1, p.survival.1));
+        sigma(n, low, p.alpha_n) * (1.0 - sigma(n, high, p.alpha_n))
+    }
+
+    pub fn step(&mut self) {
+        let m = self.inner.convolve(&self.grid);
+        let n = self.outer.convolve(&self.grid);
+        let next: Vec<f64> = n.iter().zip(&m).map(|(n, m)| self.transition(*n, *m)).collect();
+        let dt = self.params.dt;
+        for (cell, s) in self.grid.cells_mut().iter_mut().zip(next) {
+            let v = match dt {
+                Some(dt) => cell.to_f64() + dt * (2.0 * s - 1.0),
+                None => s,
+            };
+            *cell = T::from_f64(v.clamp(0.0, 1.0));
+        }
+        self.generation += 1;
+    }
+
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+
+    #[test]
+    fn growth_test() {
+        let g = Growth::Gaussian { mu: 0.15, sigma: 0.015 };
+        assert_eq!(g.apply(0.15), 1.0);
+        assert!(g.apply(0.0) < -0.99);
+        let p = Growth::Polynomial { mu: 0.3, sigma: 0.05 };
+        assert_eq!((p.apply(0.3), p.apply(0.5)), (1.0, -1.0));
+        let s = Growth::Step { mu: 0.3, sigma: 0.05 };
+        assert_eq!((s.apply(0.34), s.apply(0.36)), (1.0, -1.0));
+    }
+
+    #[test]
+    fn lenia_test() {
+        let growth = Growth::Gaussian { mu: 0.15, sigma: 0.03 };
+        let mut lenia: Lenia<f64> = Lenia::new(32, 32, Kernel::ring(8, &[1.0]), growth, 0.1);
+        // a square of cells at 0.5 grows around its edge where the potential
+        // is near mu
+        for y in 12..20 {
+            for x in 12..20 {
+                lenia.grid_mut().set(Point::new(x, y), 0.5);
+            }
+        }
+        let mut direct = lenia.clone();
+        direct.set_convolution(Convolution::Direct);
+        lenia.set_convolution(Convolution::Fft);
+        let mass = |lenia: &Lenia<f64>| lenia.grid().cells().iter().sum::<f64>();
+        let live = |lenia: &Lenia<f64>| lenia.grid().cells().iter().filter(|v| **v > 0.0).count();
+        let mut masses = vec![mass(&lenia)];
+        for _ in 0..10 {
+            lenia.step();
+            direct.step();
+            masses.push(mass(&lenia));
+        }
+        assert_eq!(lenia.generation(), 10);
+        assert!(masses.windows(2).all(|w| w[1] > w[0]), "{:?}", masses);
+        assert!(masses[10] > 2.0 * masses[0]);
+        assert!(live(&lenia) > 4 * 64);
+        let diff = lenia.grid().cells().iter().zip(direct.grid().cells());
+        assert!(diff.into_iter().all(|(a, b)| (a - b).abs() < 1e-9));
+        assert!(lenia.grid().cells().iter().all(|v| (0.0..=1.0).contains(v)));
+    }
+
+    #[test]
+    fn smooth_life_test() {
+        let params = SmoothLifeParams { inner_radius: 3.0, ..Default::default() };
+        let mut life: SmoothLife<f32> = SmoothLife::new(24, 24, params);
+        life.run(1);
+        // an empty grid stays empty
+        assert!(life.grid().cells().iter().all(|v| *v < 0.01));
+        // a full grid is overcrowded
+        life.grid_mut().fill(1.0);
+        life.run(1);
+        assert!(life.grid().cells().iter().all(|v| *v < 0.01));
+    }
+
+    #[test]
+    fn smooth_life_step_test() {
+        // the disc of radius 3 has 29 cells and the annulus out to 9 has 224,
+        // so one cell at 1 among cells at 0.3 gives itself m = 0.3 + 0.7 / 29
+        // and cells 5 away n = 0.3 + 0.7 / 224
+        let params = SmoothLifeParams { inner_radius: 3.0, ..Default::default() };
+        for method in [Convolution::Direct, Convolution::Fft] {
+            let mut life: SmoothLife<f64> = SmoothLife::new(24, 24, params);
+            life.set_convolution(method);
+            life.grid_mut().fill(0.3);
+            life.grid_mut().set(Point::new(10, 10), 1.0);
+            life.step();
+            let cell = |x, y| *life.grid().get(Point::new(x, y)).unwrap();
+            for (x, y, expected) in [(10, 10, 0.959058546776), (15, 10, 0.973166573642), (0, 22, 0.958809581143)] {
+                assert!((cell(x, y) - expected).abs() < 1e-9, "{:?} ({}, {}) is {}", method, x, y, cell(x, y));
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Radix-2 fast Fourier transform used for convolving with large kernels
+
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, o: Complex) -> Complex {
+        Complex::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, o: Complex) -> Complex {
+        Complex::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+}
+
+/// Transform `data` in place, its length must be a power of two. The
+/// inverse transform is not scaled by `1 / len`
+pub fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "fft length {} is not a power of two", n);
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f64::consts::PI / len as f64;
+        let step = Complex::new(angle.cos(), angle.sin());
+        for chunk in data.chunks_mut(len) {
+            let mut w = Complex::new(1.0, 0.0);
+            let (a, b) = chunk.split_at_mut(len / 2);
+            for (x, y) in a.iter_mut().zip(b.iter_mut()) {
+                let t = *y * w;
+                *y = *x - t;
+                *x = *x + t;
+                w = w * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Transform a `width` by `height` row-major array in place, both must be
+/// powers of two
+pub fn fft2d(data: &mut [Complex], width: usize, height: usize, inverse: bool) {
+    for row in data.chunks_mut(width) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for (y, c) in column.iter_mut().enumerate() {
+            *c = data[y * width + x];
+        }
+        fft(&mut column, inverse);
+        for (y, c) in column.iter().enumerate() {
+            data[y * width + x] = *c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_round_trip_test() {
+        let input: Vec<_> = (0..16).map(|i| Complex::new((i * i % 7) as f64, 0.0)).collect();
+        let mut data = input.clone();
+        fft2d(&mut data, 4, 4, false);
+        // the first bin is the sum of the input
+        assert!((data[0].re - input.iter().map(|c| c.re).sum::<f64>()).abs() < 1e-9);
+        fft2d(&mut data, 4, 4, true);
+        for (a, b) in data.iter().zip(&input) {
+            assert!((a.re / 16.0 - b.re).abs() < 1e-9 && (a.im / 16.0).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::continuous::fft::{fft2d, Complex};
+use crate::continuous::Real;
+use crate::grid::dense_grid::DenseGrid;
+use crate::grid::point::{IsPoint, Point};
+
+/// Kernels with a radius at least this large are convolved with an FFT by
+/// [`Convolution::Auto`]
+pub const FFT_RADIUS: usize = 8;
+
+/// Weights of the cells within `radius` of a cell, summing to 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kernel {
+    radius: usize,
+    /// offsets and weights of the cells with a non zero weight
+    weights: Vec<(Point<i32>, f64)>,
+}
+
+impl Kernel {
+    /// Build a kernel from the weight of a cell at a distance from the centre.
+    /// Weights are normalised to sum to 1, a kernel with no weight is empty
+    pub fn from_distance(radius: usize, weight: impl Fn(f64) -> f64) -> Kernel {
+        let r = radius as i32;
+        let mut weights = Vec::new();
+        for y in -r..=r {
+            for x in -r..=r {
+                let w = weight(((x * x + y * y) as f64).sqrt());
+                if w != 0.0 {
+                    weights.push((Point::new(x, y), w));
+                }
+            }
+        }
+        let total: f64 = weights.iter().map(|(_, w)| w).sum();
+        if total != 0.0 {
+            weights.iter_mut().for_each(|(_, w)| *w /= total);
+        }
+        Kernel { radius, weights }
+    }
+
+    /// Lenia kernel of concentric rings. A cell at distance `d` is in shell
+    /// `floor(d / radius * shells.len())`, whose peak is given by `shells`,
+    /// with a smooth bump across each shell
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::continuous::kernel::Kernel;
+    ///
+    /// let kernel = Kernel::ring(13, &[1.0]);
+    /// assert_eq!(kernel.radius(), 13);
+    /// assert!((kernel.weights().iter().map(|(_, w)| w).sum::<f64>() - 1.0).abs() < 1e-9);
+    /// ```
+    pub fn ring(radius: usize, shells: &[f64]) -> Kernel {
+        let bump = |x: f64| {
+            if x <= 0.0 || x >= 1.0 {
+                0.0
+            } else {
+                (4.0 - 1.0 / (x * (1.0 - x))).exp()
+            }
+        };
+        Kernel::from_distance(radius, |d| {
+            let r = d / radius.max(1) as f64 * shells.len() as f64;
+            match shells.get(r as usize) {
+                Some(peak) if d < radius as f64 => peak * bump(r.fract()),
+                _ => 0.0,
+            }
+        })
+    }
+
+    /// Every cell within `radius`, including the centre
+    pub fn disc(radius: f64) -> Kernel {
+        Kernel::from_distance(radius.ceil() as usize, |d| if d <= radius { 1.0 } else { 0.0 })
+    }
+
+    /// Every cell further than `inner` and within `outer`
+    pub fn annulus(inner: f64, outer: f64) -> Kernel {
+        Kernel::from_distance(outer.ceil() as usize, |d| if d > inner && d <= outer { 1.0 } else { 0.0 })
+    }
+
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Offsets and weights of the cells with a non zero weight
+    pub fn weights(&self) -> &[(Point<i32>, f64)] {
+        &self.weights
+    }
+
+    /// Weighted sum of the neighbourhood of every cell, cells outside of a
+    /// fixed grid are 0
+    pub fn convolve<T: Real>(&self, grid: &DenseGrid<T>) -> Vec<f64> {
+        grid.iter()
+            .map(|(p, _)| {
+                self.weights
+                    .iter()
+                    .map(|(o, w)| grid.get(p + *o).map_or(0.0, |v| v.to_f64()) * w)
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// How a [`Convolver`] computes convolutions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Convolution {
+    /// Sum the weights of every cell
+    Direct,
+    /// Multiply in the frequency domain
+    Fft,
+    /// FFT for kernels with a radius of at least [`FFT_RADIUS`]
+    #[default]
+    Auto,
+}
+
+/// A kernel along with its spectrum, cached for the size of the last grid it
+/// was convolved with
+#[derive(Clone, Debug)]
+pub struct Convolver {
+    kernel: Kernel,
+    method: Convolution,
+    spectrum: Option<(usize, usize, Vec<Complex>)>,
+}
+
+impl Convolver {
+    pub fn new(kernel: Kernel, method: Convolution) -> Convolver {
+        Convolver {
+            kernel,
+            method,
+            spectrum: None,
+        }
+    }
+
+    pub fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+
+    pub fn set_method(&mut self, method: Convolution) {
+        self.method = method;
+    }
+
+    /// Same as [`Kernel::convolve`], using the FFT if the method asks for it
+    pub fn convolve<T: Real>(&mut self, grid: &DenseGrid<T>) -> Vec<f64> {
+        let fft = match self.method {
+            Convolution::Direct => false,
+            Convolution::Fft => true,
+            Convolution::Auto => self.kernel.radius >= FFT_RADIUS,
+        };
+        if !fft {
+            return self.kernel.convolve(grid);
+        }
+
+        // pad the grid with a halo of the kernel's radius, wrapped around a
+        // toroidal grid, so the circular convolution never wraps over cells
+        let r = self.kernel.radius;
+        let (width, height) = (grid.width(), grid.height());
+        let pw = (width + 2 * r).next_power_of_two();
+        let ph = (height + 2 * r).next_power_of_two();
+        let mut data = vec![Complex::default(); pw * ph];
+        for y in 0..height + 2 * r {
+            for x in 0..width + 2 * r {
+                let p = Point::new(x as i32 - r as i32, y as i32 - r as i32);
+                data[y * pw + x].re = grid.get(p).map_or(0.0, |v| v.to_f64());
+            }
+        }
+        fft2d(&mut data, pw, ph, false);
+
+        let spectrum = match &self.spectrum {
+            Some((w, h, s)) if (*w, *h) == (pw, ph) => s,
+            _ => {
+                // the weight at offset o goes to -o to correlate rather than convolve
+                let mut s = vec![Complex::default(); pw * ph];
+                for (o, w) in self.kernel.weights.iter() {
+                    let x = (-o.x()).rem_euclid(pw as i32) as usize;
+                    let y = (-o.y()).rem_euclid(ph as i32) as usize;
+                    s[y * pw + x].re += w;
+                }
+                fft2d(&mut s, pw, ph, false);
+                &self.spectrum.insert((pw, ph, s)).2
+            }
+        };
+        data.iter_mut().zip(spectrum).for_each(|(d, s)| *d = *d * *s);
+        fft2d(&mut data, pw, ph, true);
+
+        let scale = (pw * ph) as f64;
+        let mut out = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                out.push(data[(y + r) * pw + x + r].re / scale);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::dense_grid::Boundary;
+    use crate::rng::Rng;
+
+    #[test]
+    fn kernels_test() {
+        let ring = Kernel::ring(4, &[0.5, 1.0]);
+        assert!(ring.weights().iter().all(|(o, _)| *o != Point::new(0, 0)));
+        let weight = |p| ring.weights().iter().find(|(o, _)| *o == p).map_or(0.0, |(_, w)| *w);
+        assert!(weight(Point::new(3, 0)) > weight(Point::new(1, 0)));
+        assert_eq!(Kernel::disc(1.0).weights().len(), 5);
+        assert_eq!(Kernel::annulus(1.0, 2.0).weights().len(), 8);
+    }
+
+    #[test]
+    fn fft_matches_direct_test() {
+        let mut rng = Rng::new(5);
+        for boundary in [Boundary::Fixed, Boundary::Toroidal] {
+            let mut grid: DenseGrid<f32> = DenseGrid::with_boundary(13, 9, boundary);
+            grid.cells_mut().iter_mut().for_each(|c| *c = rng.next_f64() as f32);
+            let mut direct = Convolver::new(Kernel::ring(5, &[1.0, 0.3]), Convolution::Direct);
+            let mut fft = Convolver::new(Kernel::ring(5, &[1.0, 0.3]), Convolution::Fft);
+            let (a, b) = (direct.convolve(&grid), fft.convolve(&grid));
+            assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-9));
+            // the cached spectrum is reused
+            assert_eq!(fft.convolve(&grid), b);
+        }
+    }
+}
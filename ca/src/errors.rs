@@ -1,24 +1,235 @@
-use std::{
-    error,
-    fmt::{Display, Debug, Error}
-};
+use std::{error, fmt::Display, io};
 
+use crate::grid::point::{IsPoint, Point};
+
+/// Any error returned by the crate
 #[derive(Debug)]
-pub struct SumError;
+pub enum CaError {
+    /// A state that is needed doesn't exist, e.g. an undeclared state name or
+    /// a neighbour without a state
+    MissingState(String),
+    /// A neighbourhood can't be built from its parameters
+    InvalidNeighbourhood(String),
+    /// A layer that doesn't exist, already exists or holds another type
+    InvalidLayer(String),
+    /// A pattern identifier that can't be decoded
+    InvalidPattern(String),
+    /// An image that can't be encoded, e.g. one too large for its format
+    InvalidImage(String),
+    /// A snapshot or recording that is corrupt or was written by an unknown
+    /// version
+    InvalidSnapshot(String),
+    /// Coordinates outside of a grid
+    OutOfBounds {
+        point: Point<i32>,
+        width: usize,
+        height: usize,
+    },
+    /// Invalid CA description
+    Parse(ParseError),
+    /// Invalid rule or pattern file
+    Load(LoadError),
+    Io(io::Error),
+}
 
-impl Display for SumError {
+impl Display for CaError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid item To Sum")
+        match self {
+            CaError::MissingState(context) => write!(f, "missing state: {}", context),
+            CaError::InvalidNeighbourhood(context) => write!(f, "invalid neighbourhood: {}", context),
+            CaError::InvalidLayer(context) => write!(f, "invalid layer: {}", context),
+            CaError::InvalidPattern(context) => write!(f, "invalid pattern: {}", context),
+            CaError::InvalidImage(context) => write!(f, "invalid image: {}", context),
+            CaError::InvalidSnapshot(context) => write!(f, "invalid snapshot: {}", context),
+            CaError::OutOfBounds { point, width, height } => {
+                write!(f, "({}, {}) is outside of the {}x{} grid", point.x(), point.y(), width, height)
+            }
+            CaError::Parse(e) => write!(f, "invalid CA description, {}", e),
+            CaError::Load(e) => write!(f, "invalid file, {}", e),
+            CaError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for CaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CaError::Parse(e) => Some(e),
+            CaError::Load(e) => Some(e),
+            CaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for CaError {
+    fn from(e: ParseError) -> Self {
+        CaError::Parse(e)
+    }
+}
+
+impl From<LoadError> for CaError {
+    fn from(e: LoadError) -> Self {
+        CaError::Load(e)
+    }
+}
+
+impl From<io::Error> for CaError {
+    fn from(e: io::Error) -> Self {
+        CaError::Io(e)
     }
 }
 
-impl error::Error for SumError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> { None }
-    fn cause(&self) -> Option<&dyn error::Error> { self.source() }
+/// Error loading a rule or pattern file, with the line it occurred on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    line: usize,
+    reason: String,
+}
+
+impl LoadError {
+    pub fn new(line: usize, reason: impl Into<String>) -> LoadError {
+        LoadError {
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    /// 1-indexed line the error occurred on
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
 }
 
-impl From<Error> for SumError {
-    fn from(_value: Error) -> Self {
-        SumError 
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
     }
 }
+
+impl error::Error for LoadError {}
+
+/// Error parsing a CA description, pointing at the offending token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    token: String,
+    expected: Vec<String>,
+    reason: Option<String>,
+}
+
+impl ParseError {
+    /// Error for a token that isn't any of the `expected` ones. An empty
+    /// token is the end of the input
+    pub fn unexpected(line: usize, column: usize, token: impl Into<String>, expected: Vec<String>) -> ParseError {
+        ParseError {
+            line,
+            column,
+            token: token.into(),
+            expected,
+            reason: None,
+        }
+    }
+
+    /// Error for a token that is well formed but not valid where it is, e.g.
+    /// an undeclared name
+    pub fn invalid(line: usize, column: usize, token: impl Into<String>, reason: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            column,
+            token: token.into(),
+            expected: Vec::new(),
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// 1-indexed line the offending token is on
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// 1-indexed column, in characters, the offending token starts at
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Descriptions of the tokens that would have been valid instead
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    /// Description of the error without its position
+    pub fn message(&self) -> String {
+        if let Some(reason) = &self.reason {
+            return reason.clone();
+        }
+        let found = match self.token.as_str() {
+            "" => "end of input".to_string(),
+            t => format!("'{}'", t),
+        };
+        match self.expected.split_last() {
+            None => format!("unexpected {}", found),
+            Some((last, [])) => format!("expected {} but found {}", last, found),
+            Some((last, rest)) => format!("expected {} or {} but found {}", rest.join(", "), last, found),
+        }
+    }
+
+    /// Render the error along with the line of `source` it is on, with the
+    /// offending token underlined
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::parse::parse_ca_string;
+    ///
+    /// let source = "states: a = 0\nneighbourhood: moor";
+    /// let err = parse_ca_string(source).unwrap_err();
+    /// assert_eq!(err.render(source), "\
+    /// error: expected a neighbourhood type but found 'moor'
+    ///  --> line 2, column 16
+    ///   |
+    /// 2 | neighbourhood: moor
+    ///   |                ^^^^
+    /// ");
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let text = source.lines().nth(self.line.saturating_sub(1)).unwrap_or("");
+        let gutter = " ".repeat(self.line.to_string().len());
+        let indent: String = text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = "^".repeat(self.token.chars().count().max(1));
+        format!(
+            "error: {}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}{}\n",
+            self.message(),
+            gutter,
+            self.line,
+            self.column,
+            gutter,
+            self.line,
+            text,
+            gutter,
+            indent,
+            underline
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message())
+    }
+}
+
+impl error::Error for ParseError {}
@@ -0,0 +1,77 @@
+//! Rendering grids for display. Every renderer draws states with the colours
+//! and glyphs of a [`StateSet`], falling back to its defaults for states
+//! without them
+
+pub mod gif;
+pub mod image;
+pub mod png;
+pub mod svg;
+pub mod zlib;
+
+use crate::grid::dense_grid::DenseGrid;
+use crate::state::{State, StateSet};
+
+/// Render a grid as one line of glyphs per row
+///
+/// # Examples
+///
+/// ```
+/// use ca::export;
+/// use ca::grid::dense_grid::DenseGrid;
+/// use ca::grid::point::Point;
+/// use ca::state::{State, StateSet};
+///
+/// let mut grid = DenseGrid::new(3, 2);
+/// grid.set(Point::new(1, 0), State::new(1));
+/// assert_eq!(export::text(&grid, &StateSet::new()), ".1.\n...\n");
+/// ```
+pub fn text(grid: &DenseGrid<State>, states: &StateSet) -> String {
+    let mut s = String::new();
+    for row in grid.cells().chunks(grid.width().max(1)) {
+        s.extend(row.iter().map(|c| states.glyph(*c)));
+        s.push('\n');
+    }
+    s
+}
+
+/// Render a grid for a terminal with 24-bit colour, each cell is its glyph
+/// in its state's colour
+pub fn ansi(grid: &DenseGrid<State>, states: &StateSet) -> String {
+    let mut s = String::new();
+    for row in grid.cells().chunks(grid.width().max(1)) {
+        let mut current = None;
+        for cell in row {
+            let colour = states.colour(*cell);
+            if current != Some(colour) {
+                s.push_str(&format!("\x1b[38;2;{};{};{}m", colour.0, colour.1, colour.2));
+                current = Some(colour);
+            }
+            s.push(states.glyph(*cell));
+        }
+        s.push_str("\x1b[0m\n");
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+    use crate::state::Rgb;
+
+    #[test]
+    fn ansi_test() {
+        let mut states = StateSet::new();
+        let off = states.push("off").unwrap();
+        let on = states.push("on").unwrap();
+        let info = states.info_mut(on).unwrap();
+        info.set_colour(Some(Rgb(255, 0, 0)));
+        info.set_glyph(Some('#'));
+        let mut grid = DenseGrid::new(3, 1);
+        grid.set(Point::new(1, 0), on);
+        grid.set(Point::new(2, 0), on);
+        assert_eq!(text(&grid, &states), ".##\n");
+        assert_eq!(ansi(&grid, &states), "\x1b[38;2;0;0;0m.\x1b[38;2;255;0;0m##\x1b[0m\n");
+        assert_eq!(grid.get(Point::new(0, 0)), Some(&off));
+    }
+}
@@ -0,0 +1,287 @@
+//! Animated GIFs of runs, one frame per generation
+
+use std::collections::HashMap;
+
+use crate::automaton::Automaton2D;
+use crate::errors::CaError;
+use crate::export::image::{live_bounds, ImageOptions, Raster};
+use crate::grid::dense_grid::DenseGrid;
+use crate::grid::point::{IsPoint, Point};
+use crate::rule::Rule;
+use crate::state::{Rgb, State, StateSet};
+
+/// How a run is animated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GifOptions {
+    pub image: ImageOptions,
+    /// Time every frame is shown for, in hundredths of a second
+    pub delay: u16,
+    /// Number of times the animation repeats, 0 forever and `None` to play
+    /// it once
+    pub loop_count: Option<u16>,
+}
+
+impl Default for GifOptions {
+    fn default() -> GifOptions {
+        GifOptions {
+            image: ImageOptions::default(),
+            delay: 10,
+            loop_count: Some(0),
+        }
+    }
+}
+
+/// Pack LZW codes into bytes, least significant bit first
+struct CodeWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Variable length LZW as used by GIF, starting with codes of
+/// `min_code_size + 1` bits
+fn lzw(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter { out: Vec::new(), buffer: 0, bits: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+
+    writer.write(clear, size);
+    let Some((first, rest)) = indices.split_first() else {
+        writer.write(end, size);
+        return writer.finish();
+    };
+    let mut prefix = *first as u16;
+    for index in rest {
+        if let Some(code) = table.get(&(prefix, *index)) {
+            prefix = *code;
+            continue;
+        }
+        writer.write(prefix, size);
+        if next < 4096 {
+            table.insert((prefix, *index), next);
+            next += 1;
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        } else {
+            writer.write(clear, size);
+            table.clear();
+            size = min_code_size + 1;
+            next = end + 1;
+        }
+        prefix = *index as u16;
+    }
+    writer.write(prefix, size);
+    writer.write(end, size);
+    writer.finish()
+}
+
+/// Draw grids as the frames of an animated GIF. The palette holds the colours
+/// of the states in the frames, beyond 256 colours the first is used. When
+/// cropping, every frame shows the bounding box of the cells alive in any
+/// frame. Fails if the frames are wider or taller than the 65535 pixels a GIF
+/// can hold
+pub fn gif(frames: &[DenseGrid<State>], states: &StateSet, options: &GifOptions) -> Result<Vec<u8>, CaError> {
+    let (width, height) = frames.first().map_or((0, 0), |f| (f.width() as i32, f.height() as i32));
+    let mut min = Point::new(0, 0);
+    let mut max = Point::new(width - 1, height - 1);
+    if options.image.crop {
+        let bounds: Vec<_> = frames.iter().filter_map(live_bounds).collect();
+        if !bounds.is_empty() {
+            min = Point::new(bounds.iter().map(|b| b.0.x()).min().unwrap(), bounds.iter().map(|b| b.0.y()).min().unwrap());
+            max = Point::new(bounds.iter().map(|b| b.1.x()).max().unwrap(), bounds.iter().map(|b| b.1.y()).max().unwrap());
+        }
+    }
+    let rasters: Vec<_> = frames.iter().map(|f| Raster::region(f, min, max, &options.image)).collect();
+    let (image_width, image_height) = rasters.first().map_or((0, 0), |r| (r.width, r.height));
+    let (Ok(image_width), Ok(image_height)) = (u16::try_from(image_width), u16::try_from(image_height)) else {
+        return Err(CaError::InvalidImage(format!(
+            "{}x{} pixel frames are larger than a GIF can hold",
+            image_width, image_height
+        )));
+    };
+
+    let mut palette: Vec<Rgb> = Vec::new();
+    let mut indices: HashMap<Rgb, u8> = HashMap::new();
+    for raster in rasters.iter() {
+        for c in raster.colours(states, &options.image) {
+            if !indices.contains_key(&c) && palette.len() < 256 {
+                indices.insert(c, palette.len() as u8);
+                palette.push(c);
+            }
+        }
+    }
+    // the colour table has 2^bits entries
+    let bits = (palette.len().max(2) as u32).next_power_of_two().trailing_zeros();
+    palette.resize(1 << bits, Rgb::default());
+
+    let mut out = b"GIF89a".to_vec();
+    out.extend(image_width.to_le_bytes());
+    out.extend(image_height.to_le_bytes());
+    // global colour table of 8 bit colours, no background or aspect ratio
+    out.extend([0xf0 | (bits as u8 - 1), 0, 0]);
+    out.extend(palette.iter().flat_map(|c| [c.0, c.1, c.2]));
+    if let Some(count) = options.loop_count {
+        out.extend([0x21, 0xff, 11]);
+        out.extend(b"NETSCAPE2.0");
+        out.extend([3, 1]);
+        out.extend(count.to_le_bytes());
+        out.push(0);
+    }
+
+    for raster in rasters.iter() {
+        out.extend([0x21, 0xf9, 4, 0]);
+        out.extend(options.delay.to_le_bytes());
+        out.extend([0, 0]);
+        out.push(0x2c);
+        out.extend([0, 0, 0, 0]);
+        out.extend(image_width.to_le_bytes());
+        out.extend(image_height.to_le_bytes());
+        out.push(0);
+
+        let min_code_size = bits.max(2);
+        let pixels: Vec<u8> = raster.colours(states, &options.image).map(|c| indices.get(&c).copied().unwrap_or(0)).collect();
+        out.push(min_code_size as u8);
+        for block in lzw(&pixels, min_code_size).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend(block);
+        }
+        out.push(0);
+    }
+    out.push(0x3b);
+    Ok(out)
+}
+
+/// Run `automaton` for `generations` steps and animate them, starting with
+/// the current generation, in the colours of its states. Fails like [`gif`]
+///
+/// # Examples
+///
+/// ```
+/// use ca::automaton::Automaton2D;
+/// use ca::export::gif::{record, GifOptions};
+/// use ca::rule::Rules;
+/// use ca::state::State;
+///
+/// let rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+/// let mut automaton = Automaton2D::new(8, 8, rules);
+/// let gif = record(&mut automaton, 3, &GifOptions::default()).unwrap();
+/// assert!(gif.starts_with(b"GIF89a"));
+/// assert_eq!(automaton.generation(), 3);
+/// ```
+pub fn record<R: Rule>(automaton: &mut Automaton2D<R>, generations: usize, options: &GifOptions) -> Result<Vec<u8>, CaError> {
+    let mut frames = vec![automaton.grid().clone()];
+    for _ in 0..generations {
+        automaton.step();
+        frames.push(automaton.grid().clone());
+    }
+    gif(&frames, automaton.states(), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode the codes written by `lzw`
+    fn unlzw(data: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1u16 << min_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|i| vec![i as u8]).collect();
+            table.extend([vec![], vec![]]);
+        };
+        reset(&mut table);
+        let (mut pos, mut size) = (0, min_code_size + 1);
+        let mut out = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = (0..size).fold(0usize, |acc, i| acc | (((data[(pos + i as usize) / 8] >> ((pos + i as usize) % 8)) & 1) as usize) << i);
+            pos += size as usize;
+            if code == clear as usize {
+                reset(&mut table);
+                size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear as usize + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) => [p.clone(), vec![p[0]]].concat(),
+                (None, None) => panic!("invalid code"),
+            };
+            if let Some(p) = previous {
+                table.push([p, vec![entry[0]]].concat());
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_test() {
+        let data: Vec<u8> = (0..20_000u32).map(|i| ((i / 7) % 5 + (i % 3 == 0) as u32) as u8).collect();
+        let packed = lzw(&data, 3);
+        assert!(packed.len() < data.len() / 2);
+        assert_eq!(unlzw(&packed, 3), data);
+        assert_eq!(unlzw(&lzw(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn gif_test() {
+        let mut frames = vec![DenseGrid::new(4, 4), DenseGrid::new(4, 4)];
+        frames[0].set(Point::new(1, 1), State::new(1));
+        frames[1].set(Point::new(2, 3), State::new(1));
+        let options = GifOptions {
+            image: ImageOptions { crop: true, ..Default::default() },
+            delay: 50,
+            loop_count: None,
+        };
+        let gif = gif(&frames, &StateSet::new(), &options).unwrap();
+        // a 2x3 screen with a 2 colour table
+        assert_eq!(&gif[6..11], [2, 0, 3, 0, 0xf0]);
+        assert_eq!(&gif[13..19], [255, 255, 255, 0, 0, 0]);
+        assert!(!gif.windows(11).any(|w| w == b"NETSCAPE2.0"));
+        assert_eq!(gif.windows(6).filter(|w| *w == [0x21, 0xf9, 4, 0, 50, 0]).count(), 2);
+        assert_eq!(gif.last(), Some(&0x3b));
+    }
+
+    #[test]
+    fn oversized_gif_test() {
+        // 4097 cells of 16 pixels are 65552 pixels wide
+        let frames = [DenseGrid::new(4097, 1)];
+        let options = GifOptions {
+            image: ImageOptions { cell_size: 16, ..Default::default() },
+            ..Default::default()
+        };
+        let err = gif(&frames, &StateSet::new(), &options).unwrap_err();
+        assert!(matches!(err, CaError::InvalidImage(_)));
+        assert_eq!(err.to_string(), "invalid image: 65552x16 pixel frames are larger than a GIF can hold");
+    }
+}
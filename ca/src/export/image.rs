@@ -0,0 +1,162 @@
+//! Raster images of grids, each cell drawn as a square of its state's colour
+
+use crate::grid::dense_grid::DenseGrid;
+use crate::grid::point::{IsPoint, Point};
+use crate::state::{Rgb, State, StateSet};
+
+/// How a grid is drawn as an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Width and height of a cell in pixels
+    pub cell_size: usize,
+    /// Colour of 1 pixel wide lines drawn around every cell
+    pub grid_lines: Option<Rgb>,
+    /// Only draw the bounding box of the cells not in state 0, an empty grid
+    /// is drawn whole
+    pub crop: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> ImageOptions {
+        ImageOptions {
+            cell_size: 1,
+            grid_lines: None,
+            crop: false,
+        }
+    }
+}
+
+/// Pixels of a drawn grid, row by row, `None` for grid lines
+pub(crate) struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Option<State>>,
+}
+
+/// Corners of the bounding box of the cells not in state 0
+pub(crate) fn live_bounds(grid: &DenseGrid<State>) -> Option<(Point<i32>, Point<i32>)> {
+    let live: Vec<_> = grid.iter().filter(|(_, s)| s.id() != 0).map(|(p, _)| p).collect();
+    let min = Point::new(live.iter().map(|p| p.x()).min()?, live.iter().map(|p| p.y()).min()?);
+    let max = Point::new(live.iter().map(|p| p.x()).max()?, live.iter().map(|p| p.y()).max()?);
+    Some((min, max))
+}
+
+impl Raster {
+    pub fn new(grid: &DenseGrid<State>, options: &ImageOptions) -> Raster {
+        let whole = (Point::new(0, 0), Point::new(grid.width() as i32 - 1, grid.height() as i32 - 1));
+        let (min, max) = if options.crop { live_bounds(grid).unwrap_or(whole) } else { whole };
+        Raster::region(grid, min, max, options)
+    }
+
+    /// Draw the cells from `min` to `max` inclusive, cells outside of the
+    /// grid are in state 0
+    pub fn region(grid: &DenseGrid<State>, min: Point<i32>, max: Point<i32>, options: &ImageOptions) -> Raster {
+        let (columns, rows) = ((max.x() - min.x() + 1).max(0) as usize, (max.y() - min.y() + 1).max(0) as usize);
+        let cell = options.cell_size.max(1);
+        let line = options.grid_lines.is_some() as usize;
+        let pitch = cell + line;
+        // cell a pixel coordinate falls in, None on a grid line
+        let index = |pixel: usize| {
+            let offset = pixel % pitch;
+            (line == 0 || offset != 0).then_some(pixel / pitch)
+        };
+
+        let (width, height) = (columns * pitch + line, rows * pitch + line);
+        let mut pixels = Vec::with_capacity(width * height);
+        for py in 0..height {
+            for px in 0..width {
+                pixels.push(match (index(px), index(py)) {
+                    (Some(x), Some(y)) if x < columns && y < rows => {
+                        Some(grid.get(min + Point::new(x as i32, y as i32)).copied().unwrap_or_default())
+                    }
+                    _ => None,
+                });
+            }
+        }
+        Raster { width, height, pixels }
+    }
+
+    pub fn colours<'a>(&'a self, states: &'a StateSet, options: &'a ImageOptions) -> impl Iterator<Item = Rgb> + 'a {
+        self.pixels.iter().map(|p| match p {
+            Some(s) => states.colour(*s),
+            None => options.grid_lines.unwrap_or_default(),
+        })
+    }
+}
+
+/// Grey level of a colour
+fn luma(c: Rgb) -> u8 {
+    ((299 * c.0 as u32 + 587 * c.1 as u32 + 114 * c.2 as u32) / 1000) as u8
+}
+
+/// Draw a grid as a binary PPM (`P6`) image
+///
+/// # Examples
+///
+/// ```
+/// use ca::export::image::{ppm, ImageOptions};
+/// use ca::grid::dense_grid::DenseGrid;
+/// use ca::state::StateSet;
+///
+/// let grid = DenseGrid::new(4, 3);
+/// let options = ImageOptions { cell_size: 2, ..Default::default() };
+/// let image = ppm(&grid, &StateSet::new(), &options);
+/// assert!(image.starts_with(b"P6\n8 6\n255\n"));
+/// assert_eq!(image.len(), 11 + 8 * 6 * 3);
+/// ```
+pub fn ppm(grid: &DenseGrid<State>, states: &StateSet, options: &ImageOptions) -> Vec<u8> {
+    let raster = Raster::new(grid, options);
+    let mut image = format!("P6\n{} {}\n255\n", raster.width, raster.height).into_bytes();
+    for c in raster.colours(states, options) {
+        image.extend([c.0, c.1, c.2]);
+    }
+    image
+}
+
+/// Draw a grid as a binary PGM (`P5`) image, in the grey level of each
+/// state's colour
+pub fn pgm(grid: &DenseGrid<State>, states: &StateSet, options: &ImageOptions) -> Vec<u8> {
+    let raster = Raster::new(grid, options);
+    let mut image = format!("P5\n{} {}\n255\n", raster.width, raster.height).into_bytes();
+    image.extend(raster.colours(states, options).map(luma));
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_test() {
+        let mut grid = DenseGrid::new(6, 5);
+        grid.set(Point::new(2, 1), State::new(1));
+        grid.set(Point::new(3, 2), State::new(2));
+        let mut states = StateSet::new();
+        states.push("dead");
+        states.push("alive");
+        let red = states.push("red").unwrap();
+        states.info_mut(red).unwrap().set_colour(Some(Rgb(255, 0, 0)));
+
+        let options = ImageOptions {
+            cell_size: 2,
+            grid_lines: Some(Rgb(0, 0, 255)),
+            crop: true,
+        };
+        // 2x2 cells of 2 pixels and 3 lines
+        let image = ppm(&grid, &states, &options);
+        let header = b"P6\n7 7\n255\n";
+        assert!(image.starts_with(header));
+        let pixel = |x: usize, y: usize| &image[header.len() + (y * 7 + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), [0, 0, 255]);
+        assert_eq!(pixel(1, 1), [255, 255, 255]);
+        assert_eq!(pixel(4, 1), [0, 0, 0]);
+        assert_eq!(pixel(5, 5), [255, 0, 0]);
+        assert_eq!(pixel(3, 5), [0, 0, 255]);
+
+        let image = pgm(&grid, &states, &ImageOptions::default());
+        let header = b"P5\n6 5\n255\n";
+        assert_eq!(image.len(), header.len() + 30);
+        assert_eq!(image[header.len() + 6 + 2], 255);
+        assert_eq!(image[header.len() + 2 * 6 + 3], 76);
+    }
+}
@@ -0,0 +1,95 @@
+//! PNG images of grids, compressed with the in-crate [`zlib`](super::zlib)
+
+use std::collections::HashMap;
+
+use crate::export::image::{ImageOptions, Raster};
+use crate::export::zlib::{self, Compression};
+use crate::grid::dense_grid::DenseGrid;
+use crate::state::{Rgb, State, StateSet};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = zlib::crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Draw a grid as a PNG image. The image uses a palette of the state colours
+/// when there are at most 256 of them, and 24-bit colour otherwise
+///
+/// # Examples
+///
+/// ```
+/// use ca::export::image::ImageOptions;
+/// use ca::export::png::png;
+/// use ca::grid::dense_grid::DenseGrid;
+/// use ca::state::StateSet;
+///
+/// let image = png(&DenseGrid::new(64, 64), &StateSet::new(), &ImageOptions::default());
+/// assert!(image.starts_with(b"\x89PNG\r\n\x1a\n"));
+/// ```
+pub fn png(grid: &DenseGrid<State>, states: &StateSet, options: &ImageOptions) -> Vec<u8> {
+    let raster = Raster::new(grid, options);
+    let colours: Vec<Rgb> = raster.colours(states, options).collect();
+    let mut palette = Vec::new();
+    let mut indices = HashMap::new();
+    for c in colours.iter() {
+        if !indices.contains_key(c) {
+            indices.insert(*c, palette.len());
+            palette.push(*c);
+        }
+    }
+    let indexed = palette.len() <= 256;
+
+    // every row starts with filter type 0, no filter
+    let mut data = Vec::new();
+    for row in colours.chunks(raster.width.max(1)) {
+        data.push(0);
+        for c in row {
+            if indexed {
+                data.push(indices[c] as u8);
+            } else {
+                data.extend([c.0, c.1, c.2]);
+            }
+        }
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    let mut header = Vec::new();
+    header.extend((raster.width as u32).to_be_bytes());
+    header.extend((raster.height as u32).to_be_bytes());
+    // 8 bits per sample, palette or truecolour, deflate, adaptive filters, no interlace
+    header.extend([8, if indexed { 3 } else { 2 }, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+    if indexed {
+        let plte: Vec<u8> = palette.iter().flat_map(|c| [c.0, c.1, c.2]).collect();
+        chunk(&mut out, b"PLTE", &plte);
+    }
+    chunk(&mut out, b"IDAT", &zlib::compress(&data, Compression::Deflate));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+
+    #[test]
+    fn png_test() {
+        let mut grid = DenseGrid::new(3, 2);
+        grid.set(Point::new(1, 0), State::new(1));
+        let image = png(&grid, &StateSet::new(), &ImageOptions::default());
+        // IHDR of a 3x2 paletted image
+        assert_eq!(&image[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&image[16..29], [0, 0, 0, 3, 0, 0, 0, 2, 8, 3, 0, 0, 0]);
+        // black then white
+        assert_eq!(&image[33..47], b"\0\0\0\x06PLTE\0\0\0\xff\xff\xff");
+        assert_eq!(u32::from_be_bytes(image[29..33].try_into().unwrap()), zlib::crc32(&image[12..29]));
+        assert!(image.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+    }
+}
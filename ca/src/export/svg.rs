@@ -0,0 +1,170 @@
+//! SVG images of grids and spacetime diagrams. State 0 is the background and
+//! every horizontal run of cells in another state is a single rectangle
+
+use std::fmt::Write;
+
+use crate::grid::dense_grid::DenseGrid;
+use crate::grid::point::Point;
+use crate::state::{Rgb, State, StateSet};
+
+/// Size of the coordinate labels in pixels
+const FONT_SIZE: usize = 10;
+/// Space left above and to the left of the cells for labels
+const LABEL_MARGIN: usize = 3 * FONT_SIZE;
+
+/// How a grid is drawn as an SVG image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// Width and height of a cell in pixels
+    pub cell_size: usize,
+    /// Colour of lines drawn around every cell
+    pub grid_lines: Option<Rgb>,
+    /// Label every nth column and row with its coordinate, for spacetime
+    /// diagrams rows are labelled with their generation
+    pub labels: Option<usize>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            cell_size: 10,
+            grid_lines: None,
+            labels: None,
+        }
+    }
+}
+
+/// Draw a grid as an SVG image
+///
+/// # Examples
+///
+/// ```
+/// use ca::export::svg::{svg, SvgOptions};
+/// use ca::grid::dense_grid::DenseGrid;
+/// use ca::state::StateSet;
+///
+/// let image = svg(&DenseGrid::new(4, 3), &StateSet::new(), &SvgOptions::default());
+/// assert!(image.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"40\" height=\"30\""));
+/// ```
+pub fn svg(grid: &DenseGrid<State>, states: &StateSet, options: &SvgOptions) -> String {
+    let cell = options.cell_size.max(1);
+    let margin = if options.labels.is_some() { LABEL_MARGIN } else { 0 };
+    let (width, height) = (grid.width() * cell, grid.height() * cell);
+
+    let mut s = String::new();
+    let _ = writeln!(
+        s,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" shape-rendering=\"crispEdges\">",
+        w = width + margin,
+        h = height + margin
+    );
+    let _ = writeln!(s, "<g transform=\"translate({m} {m})\">", m = margin);
+    let _ = writeln!(s, "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>", width, height, states.colour(State::new(0)));
+    for (y, row) in grid.cells().chunks(grid.width().max(1)).enumerate() {
+        let mut x = 0;
+        for run in row.chunk_by(|a, b| a == b) {
+            if run[0].id() != 0 {
+                let _ = writeln!(
+                    s,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    x * cell,
+                    y * cell,
+                    run.len() * cell,
+                    cell,
+                    states.colour(run[0])
+                );
+            }
+            x += run.len();
+        }
+    }
+
+    if let Some(colour) = options.grid_lines {
+        let mut path = String::new();
+        for x in 0..=grid.width() {
+            let _ = write!(path, "M{} 0V{}", x * cell, height);
+        }
+        for y in 0..=grid.height() {
+            let _ = write!(path, "M0 {}H{}", y * cell, width);
+        }
+        let _ = writeln!(s, "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1\"/>", path, colour);
+    }
+    s.push_str("</g>\n");
+
+    if let Some(every) = options.labels {
+        let every = every.max(1);
+        let _ = writeln!(s, "<g font-family=\"monospace\" font-size=\"{}\">", FONT_SIZE);
+        for x in (0..grid.width()).step_by(every) {
+            let _ = writeln!(
+                s,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                margin + x * cell + cell / 2,
+                margin - FONT_SIZE / 2,
+                x
+            );
+        }
+        for y in (0..grid.height()).step_by(every) {
+            let _ = writeln!(
+                s,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>",
+                margin - FONT_SIZE / 2,
+                margin + y * cell + cell / 2,
+                y
+            );
+        }
+        s.push_str("</g>\n");
+    }
+    s.push_str("</svg>\n");
+    s
+}
+
+/// Draw the generations of a one dimensional automaton as an SVG image, one
+/// row per generation from the top. Rows shorter than the longest are padded
+/// with state 0
+pub fn spacetime(generations: &[Vec<State>], states: &StateSet, options: &SvgOptions) -> String {
+    let width = generations.iter().map(Vec::len).max().unwrap_or(0);
+    let mut grid = DenseGrid::new(width, generations.len());
+    for (y, row) in generations.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            grid.set(Point::new(x as i32, y as i32), *cell);
+        }
+    }
+    svg(&grid, states, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg_test() {
+        let mut states = StateSet::new();
+        states.push("dead");
+        states.push("alive");
+        let red = states.push("red").unwrap();
+        states.info_mut(red).unwrap().set_colour(Some(Rgb(255, 0, 0)));
+        let row = |cells: &[u8]| cells.iter().map(|c| State::new(*c)).collect::<Vec<_>>();
+        let generations = [row(&[0, 1, 1, 1, 0, 2]), row(&[1, 0, 2, 2])];
+
+        let image = spacetime(&generations, &states, &SvgOptions { cell_size: 4, ..Default::default() });
+        // the background and one rectangle per run
+        assert_eq!(image.matches("<rect").count(), 5);
+        assert!(image.contains("<rect width=\"24\" height=\"8\" fill=\"#000000\"/>"));
+        assert!(image.contains("<rect x=\"4\" y=\"0\" width=\"12\" height=\"4\" fill=\"#ffffff\"/>"));
+        assert!(image.contains("<rect x=\"8\" y=\"4\" width=\"8\" height=\"4\" fill=\"#ff0000\"/>"));
+        assert!(!image.contains("<path"));
+
+        let options = SvgOptions {
+            cell_size: 4,
+            grid_lines: Some(Rgb(128, 128, 128)),
+            labels: Some(5),
+        };
+        let image = spacetime(&generations, &states, &options);
+        assert!(image.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"54\" height=\"38\""));
+        assert!(image.contains("<path d=\"M0 0V8M4 0V8"));
+        assert!(image.contains("stroke=\"#808080\""));
+        // columns 0 and 5, row 0
+        assert_eq!(image.matches("<text").count(), 3);
+        assert!(image.contains("<text x=\"52\" y=\"25\" text-anchor=\"middle\">5</text>"));
+        assert!(image.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,279 @@
+//! zlib streams and the checksums used by PNG. Data is compressed with
+//! DEFLATE using LZ77 and the fixed Huffman codes, or stored as is
+
+/// How [`compress`] encodes data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Uncompressed blocks
+    Stored,
+    /// LZ77 matches with the fixed Huffman codes
+    #[default]
+    Deflate,
+}
+
+/// CRC-32 as used by PNG chunks and gzip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Adler-32, the checksum ending a zlib stream
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Wrap `data` in a zlib stream
+///
+/// # Examples
+///
+/// ```
+/// use ca::export::zlib::{compress, Compression};
+///
+/// let data = vec![7; 1000];
+/// assert_eq!(compress(&data, Compression::Stored).len(), 2 + 5 + 1000 + 4);
+/// assert!(compress(&data, Compression::Deflate).len() < 20);
+/// ```
+pub fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+    // 32K window, no preset dictionary, header a multiple of 31
+    let mut out = vec![0x78, 0x01];
+    match compression {
+        Compression::Stored => stored(data, &mut out),
+        Compression::Deflate => {
+            let mut bits = BitWriter { out, bit: 0, byte: 0 };
+            deflate(data, &mut bits);
+            out = bits.finish();
+        }
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn stored(data: &[u8], out: &mut Vec<u8>) {
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        out.push(blocks.peek().is_none() as u8);
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+}
+
+/// Writes bits least significant first, as DEFLATE expects
+struct BitWriter {
+    out: Vec<u8>,
+    bit: u32,
+    byte: u8,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        for i in 0..count {
+            self.byte |= (((value >> i) & 1) as u8) << self.bit;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.out.push(self.byte);
+                self.bit = 0;
+                self.byte = 0;
+            }
+        }
+    }
+
+    /// Huffman codes are written most significant bit first
+    fn code(&mut self, code: u32, len: u32) {
+        let reversed = code.reverse_bits() >> (32 - len);
+        self.bits(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit > 0 {
+            self.out.push(self.byte);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+/// Candidates checked for every match
+const MAX_CHAIN: usize = 64;
+
+/// Fixed Huffman code of a literal, length or end of block symbol
+fn literal(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.code(0x30 + symbol, 8),
+        144..=255 => bits.code(0x190 + symbol - 144, 9),
+        256..=279 => bits.code(symbol - 256, 7),
+        _ => bits.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// A single final block of fixed Huffman codes
+fn deflate(data: &[u8], bits: &mut BitWriter) {
+    bits.bits(1, 1);
+    bits.bits(1, 2);
+
+    // most recent position of every 3 byte sequence, and the previous
+    // position with the same hash for every position
+    let hash = |i: usize| ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff;
+    let mut head = vec![usize::MAX; 0x8000];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, i - candidate);
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= 3 {
+            let code = LENGTH_BASE.iter().rposition(|b| *b as usize <= best_len).unwrap();
+            literal(bits, 257 + code as u32);
+            bits.bits((best_len - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+            let code = DISTANCE_BASE.iter().rposition(|b| *b as usize <= best_dist).unwrap();
+            bits.code(code as u32, 5);
+            bits.bits((best_dist - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            literal(bits, data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    literal(bits, 256);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoder for the blocks written by `compress`
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        let data = &stream[2..stream.len() - 4];
+        let mut pos = 0;
+        let mut bit = |n: u32| {
+            let mut v = 0;
+            for i in 0..n {
+                v |= (((data[pos / 8] >> (pos % 8)) & 1) as u32) << i;
+                pos += 1;
+            }
+            v
+        };
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let last = bit(1);
+            match bit(2) {
+                0 => panic!("stored blocks are checked byte by byte"),
+                1 => loop {
+                    // read the fixed Huffman code one bit at a time
+                    let mut code = 0;
+                    let mut len = 0;
+                    let symbol = loop {
+                        code = (code << 1) | bit(1);
+                        len += 1;
+                        match (len, code) {
+                            (7, 0..=0x17) => break code + 256,
+                            (8, 0x30..=0xbf) => break code - 0x30,
+                            (8, 0xc0..=0xc7) => break code - 0xc0 + 280,
+                            (9, 0x190..=0x1ff) => break code - 0x190 + 144,
+                            _ => {}
+                        }
+                    };
+                    match symbol {
+                        0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        _ => {
+                            let c = symbol as usize - 257;
+                            let len = LENGTH_BASE[c] as usize + bit(LENGTH_EXTRA[c] as u32) as usize;
+                            let c = (0..5).fold(0, |acc, _| (acc << 1) | bit(1)) as usize;
+                            let dist = DISTANCE_BASE[c] as usize + bit(DISTANCE_EXTRA[c] as u32) as usize;
+                            for _ in 0..len {
+                                out.push(out[out.len() - dist]);
+                            }
+                        }
+                    }
+                },
+                _ => panic!("dynamic blocks aren't written"),
+            }
+            if last == 1 {
+                break;
+            }
+        }
+        assert_eq!(u32::from_be_bytes(stream[stream.len() - 4..].try_into().unwrap()), adler32(&out));
+        out
+    }
+
+    #[test]
+    fn checksums_test() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn deflate_test() {
+        let mut data: Vec<u8> = (0..5000u32).map(|i| (i * i % 251) as u8).collect();
+        data.extend(std::iter::repeat_n(0, 40_000));
+        data.extend(b"abcabcabcabd, the end");
+        let compressed = compress(&data, Compression::Deflate);
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(inflate(&compressed), data);
+        assert_eq!(inflate(&compress(&[], Compression::Deflate)), Vec::<u8>::new());
+
+        let stored = compress(b"hi", Compression::Stored);
+        assert_eq!(stored, [0x78, 0x01, 1, 2, 0, 0xfd, 0xff, b'h', b'i', 1, 0x3b, 0, 0xd2]);
+    }
+}
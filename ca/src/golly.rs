@@ -0,0 +1,125 @@
+//! Loaders for the rule formats used by [Golly](https://golly.sourceforge.io)
+//!
+//! A `.rule` file starts with a `@RULE <name>` line and is split into
+//! sections by lines starting with `@` (`@TABLE`, `@TREE`, `@COLORS`, ...).
+
+pub mod rule_table;
+pub mod rule_tree;
+
+use crate::errors::LoadError;
+use crate::grid::point::Point;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::State;
+use rule_table::RuleTable;
+use rule_tree::RuleTree;
+
+/// A rule loaded from either the `@TABLE` or the `@TREE` section of a `.rule`
+/// file
+#[derive(Clone, Debug)]
+pub enum GollyRule {
+    Table(RuleTable),
+    Tree(RuleTree),
+}
+
+impl GollyRule {
+    /// Load the `@TABLE` section of a `.rule` file, or its `@TREE` section if
+    /// it has no table
+    pub fn from_rule_file(rule_file: &str) -> Result<GollyRule, LoadError> {
+        if section(rule_file, "@TABLE").is_some() {
+            RuleTable::from_rule_file(rule_file).map(GollyRule::Table)
+        } else if section(rule_file, "@TREE").is_some() {
+            RuleTree::from_rule_file(rule_file).map(GollyRule::Tree)
+        } else {
+            Err(LoadError::new(1, "no @TABLE or @TREE section"))
+        }
+    }
+}
+
+impl Rule for GollyRule {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        match self {
+            GollyRule::Table(t) => t.neighbourhood(),
+            GollyRule::Tree(t) => t.neighbourhood(),
+        }
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], point: Point<i32>, generation: u64, rng: &mut Rng) -> State {
+        match self {
+            GollyRule::Table(t) => t.apply(state, neighbours, point, generation, rng),
+            GollyRule::Tree(t) => t.apply(state, neighbours, point, generation, rng),
+        }
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        match self {
+            GollyRule::Table(t) => t.n_states(),
+            GollyRule::Tree(t) => t.n_states(),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// Return the name given on the `@RULE` line of a `.rule` file
+pub fn rule_name(rule_file: &str) -> Option<&str> {
+    rule_file
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("@RULE"))
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+}
+
+/// Return the body of a section of a `.rule` file (e.g. `"@TABLE"`) along
+/// with the 1-indexed line number the body starts on
+pub fn section<'a>(rule_file: &'a str, name: &str) -> Option<(usize, &'a str)> {
+    let mut start = None;
+    let mut offset = 0;
+    for (i, line) in rule_file.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        match start {
+            None if trimmed.split_whitespace().next() == Some(name) => {
+                start = Some((i + 2, offset + line.len()));
+            }
+            Some((first_line, begin)) if trimmed.starts_with('@') => {
+                return Some((first_line, &rule_file[begin..offset]));
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+    start.map(|(first_line, begin)| (first_line, &rule_file[begin..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::next_ids;
+
+    const RULE: &str = "@RULE Test\n\n@TABLE\nn_states:2\n\n@COLORS\n1 255 255 255\n";
+
+    #[test]
+    fn rule_name_test() {
+        assert_eq!(rule_name(RULE), Some("Test"));
+        assert_eq!(rule_name("@TABLE\n"), None);
+    }
+
+    #[test]
+    fn golly_rule_test() {
+        let tree = "@RULE W\n@TREE\nnum_states=2\nnum_neighbors=4\nnum_nodes=8\n\
+            1 0 0\n1 1 1\n2 0 0\n2 1 1\n3 2 2\n3 3 3\n4 4 5\n5 6 6\n";
+        let rule = GollyRule::from_rule_file(tree).unwrap();
+        assert!(matches!(rule, GollyRule::Tree(_)));
+        assert_eq!(next_ids(&rule, 0, &[0, 1, 0, 0]), 1);
+        assert!(GollyRule::from_rule_file(RULE).is_err());
+    }
+
+    #[test]
+    fn section_test() {
+        assert_eq!(section(RULE, "@TABLE"), Some((4, "n_states:2\n\n")));
+        assert_eq!(section(RULE, "@COLORS"), Some((7, "1 255 255 255\n")));
+        assert_eq!(section(RULE, "@TREE"), None);
+    }
+}
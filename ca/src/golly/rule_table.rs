@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use crate::errors::LoadError;
+use crate::golly;
+use crate::grid::neighbourhood::NType;
+use crate::grid::point::Point;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::State;
+
+/// Neighbour order of a Moore transition, starting north and going clockwise
+const MOORE: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+/// Neighbour order of a von Neumann transition, starting north and going
+/// clockwise
+const VON_NEUMANN: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Neighbour order of a hexagonal transition, starting north and going
+/// clockwise
+const HEXAGONAL: [(i32, i32); 6] = [(0, -1), (1, 0), (1, 1), (0, 1), (-1, 0), (-1, -1)];
+
+/// Symmetries applied to every transition of a RuleTable
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    Rotate2,
+    Rotate3,
+    Rotate4,
+    Rotate4Reflect,
+    Rotate6,
+    Rotate6Reflect,
+    Rotate8,
+    Rotate8Reflect,
+    ReflectHorizontal,
+    /// Only the number of neighbours in each state matters
+    Permute,
+}
+
+impl Symmetry {
+    /// Parse the value of a `symmetries:` line, `None` if it isn't valid for
+    /// the neighbourhood
+    fn parse(s: &str, n_type: NType) -> Option<Symmetry> {
+        let sym = match s {
+            "none" => Symmetry::None,
+            "rotate2" => Symmetry::Rotate2,
+            "rotate3" => Symmetry::Rotate3,
+            "rotate4" => Symmetry::Rotate4,
+            "rotate4reflect" => Symmetry::Rotate4Reflect,
+            "rotate6" => Symmetry::Rotate6,
+            "rotate6reflect" => Symmetry::Rotate6Reflect,
+            "rotate8" => Symmetry::Rotate8,
+            "rotate8reflect" => Symmetry::Rotate8Reflect,
+            "reflect_horizontal" => Symmetry::ReflectHorizontal,
+            "permute" => Symmetry::Permute,
+            _ => return None,
+        };
+        let valid = match sym {
+            Symmetry::None | Symmetry::Permute => true,
+            Symmetry::Rotate2 | Symmetry::Rotate3 | Symmetry::Rotate6 | Symmetry::Rotate6Reflect => {
+                n_type == NType::Hexagonal
+            }
+            Symmetry::Rotate4 | Symmetry::Rotate4Reflect | Symmetry::ReflectHorizontal => {
+                n_type == NType::Moore || n_type == NType::VonNeumann
+            }
+            Symmetry::Rotate8 | Symmetry::Rotate8Reflect => n_type == NType::Moore,
+        };
+        valid.then_some(sym)
+    }
+
+    /// Permutations of the `n` neighbour positions produced by the symmetry,
+    /// rotations are clockwise and reflections are about the north-south axis
+    fn permutations(&self, n: usize) -> Vec<Vec<usize>> {
+        let (rotations, reflect) = match self {
+            Symmetry::None | Symmetry::Permute => (1, false),
+            Symmetry::Rotate2 => (2, false),
+            Symmetry::Rotate3 => (3, false),
+            Symmetry::Rotate4 => (4, false),
+            Symmetry::Rotate4Reflect => (4, true),
+            Symmetry::Rotate6 => (6, false),
+            Symmetry::Rotate6Reflect => (6, true),
+            Symmetry::Rotate8 => (8, false),
+            Symmetry::Rotate8Reflect => (8, true),
+            Symmetry::ReflectHorizontal => (1, true),
+        };
+        let step = n / rotations;
+        let mut perms = Vec::new();
+        for r in 0..rotations {
+            let rotated: Vec<usize> = (0..n).map(|i| (i + r * step) % n).collect();
+            if reflect {
+                perms.push(rotated.iter().map(|i| (n - i) % n).collect());
+            }
+            perms.push(rotated);
+        }
+        perms
+    }
+}
+
+/// Set of states, able to hold all 256 states of a RuleTable
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct StateMask([u64; 4]);
+
+impl StateMask {
+    fn from_states(states: &[u8]) -> StateMask {
+        let mut mask = StateMask::default();
+        for s in states {
+            mask.0[*s as usize / 64] |= 1 << (s % 64);
+        }
+        mask
+    }
+
+    fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] & (1 << (state % 64)) != 0
+    }
+}
+
+/// A single transition with every bound variable substituted
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct TableTransition {
+    state: StateMask,
+    neighbours: Vec<StateMask>,
+    output: u8,
+    permute: bool,
+}
+
+impl TableTransition {
+    fn matches(&self, neighbours: &[State]) -> bool {
+        if self.permute {
+            assign(&self.neighbours, neighbours, 0)
+        } else {
+            self.neighbours.iter().zip(neighbours).all(|(m, n)| m.contains(n.id()))
+        }
+    }
+}
+
+/// Check if each neighbour can be matched to a different unused mask
+fn assign(masks: &[StateMask], neighbours: &[State], used: u32) -> bool {
+    let Some((&n, rest)) = neighbours.split_first() else {
+        return true;
+    };
+    (0..masks.len()).any(|i| {
+        used & (1 << i) == 0
+            && masks[i].contains(n.id())
+            // identical unused masks are interchangeable, only try the first
+            && !(0..i).any(|j| used & (1 << j) == 0 && masks[j] == masks[i])
+            && assign(masks, rest, used | (1 << i))
+    })
+}
+
+/// One entry of a transition line
+enum Entry {
+    Var(String),
+    States(Vec<u8>),
+}
+
+/// A rule loaded from the `@TABLE` section of a Golly `.rule` file.
+///
+/// Transitions are checked in the order they were written and the first
+/// match decides the next state; a cell matching no transition keeps its
+/// state.
+///
+/// # Examples
+///
+/// ```
+/// use ca::golly::rule_table::RuleTable;
+/// use ca::rule::Rule;
+/// use ca::state::State;
+///
+/// let table = RuleTable::parse("
+/// n_states:2
+/// neighborhood:vonNeumann
+/// symmetries:rotate4
+/// 0,1,0,0,0,1
+/// ").unwrap();
+/// let [off, on] = [State::new(0), State::new(1)];
+/// assert_eq!(table.next_state(off, &[off, off, on, off]), on);
+/// assert_eq!(table.next_state(off, &[on, on, off, off]), off);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuleTable {
+    n_states: usize,
+    n_type: NType,
+    symmetry: Symmetry,
+    neighbourhood: Vec<Point<i32>>,
+    transitions: Vec<TableTransition>,
+    by_state: Vec<Vec<usize>>,
+}
+
+impl RuleTable {
+    /// Parse the contents of a `.table` file or the body of a `@TABLE`
+    /// section
+    pub fn parse(table: &str) -> Result<RuleTable, LoadError> {
+        Self::parse_from_line(table, 1)
+    }
+
+    /// Parse the `@TABLE` section of a `.rule` file
+    pub fn from_rule_file(rule_file: &str) -> Result<RuleTable, LoadError> {
+        let (first_line, table) =
+            golly::section(rule_file, "@TABLE").ok_or_else(|| LoadError::new(1, "no @TABLE section"))?;
+        Self::parse_from_line(table, first_line)
+    }
+
+    fn parse_from_line(table: &str, first_line: usize) -> Result<RuleTable, LoadError> {
+        let mut n_states = None;
+        let mut n_type = None;
+        let mut symmetry = Symmetry::None;
+        let mut vars: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut transitions = Vec::new();
+
+        for (i, line) in table.lines().enumerate() {
+            let line_no = first_line + i;
+            let err = |reason: String| LoadError::new(line_no, reason);
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "n_states" => {
+                        let n = value.parse::<usize>().ok().filter(|n| (2..=256).contains(n));
+                        n_states = Some(n.ok_or_else(|| err(format!("invalid n_states '{}'", value)))?);
+                    }
+                    "neighborhood" | "neighbourhood" => {
+                        n_type = Some(match value {
+                            "Moore" => NType::Moore,
+                            "vonNeumann" => NType::VonNeumann,
+                            "hexagonal" => NType::Hexagonal,
+                            _ => return Err(err(format!("unsupported neighborhood '{}'", value))),
+                        });
+                    }
+                    "symmetries" => {
+                        let n_type = n_type.ok_or_else(|| err("symmetries before neighborhood".into()))?;
+                        symmetry = Symmetry::parse(value, n_type)
+                            .ok_or_else(|| err(format!("invalid symmetries '{}' for {:?}", value, n_type)))?;
+                    }
+                    key => return Err(err(format!("unknown key '{}'", key))),
+                }
+                continue;
+            }
+
+            let n_states = n_states.ok_or_else(|| err("n_states must be declared first".into()))?;
+            if let Some(var) = line.strip_prefix("var ") {
+                let (name, value) = var.split_once('=').ok_or_else(|| err("expected '='".into()))?;
+                let name = name.trim();
+                if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(err(format!("invalid variable name '{}'", name)));
+                }
+                let states = parse_set(value.trim(), &vars, n_states).map_err(err)?;
+                vars.insert(name.to_string(), states);
+                continue;
+            }
+
+            let n_type = n_type.ok_or_else(|| err("neighborhood must be declared first".into()))?;
+            let n_neighbours = neighbour_order(n_type).len();
+            let entries = split_entries(line)
+                .into_iter()
+                .map(|e| parse_entry(e, &vars, n_states))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(err)?;
+            if entries.len() != n_neighbours + 2 {
+                return Err(err(format!(
+                    "expected {} entries but found {}",
+                    n_neighbours + 2,
+                    entries.len()
+                )));
+            }
+            expand(&entries, &vars, symmetry, &mut transitions).map_err(err)?;
+        }
+
+        let line_no = first_line + table.lines().count();
+        let n_states = n_states.ok_or_else(|| LoadError::new(line_no, "missing n_states"))?;
+        let n_type = n_type.ok_or_else(|| LoadError::new(line_no, "missing neighborhood"))?;
+        let mut by_state = vec![Vec::new(); n_states];
+        for (i, t) in transitions.iter().enumerate() {
+            for (s, indices) in by_state.iter_mut().enumerate() {
+                if t.state.contains(s as u8) {
+                    indices.push(i);
+                }
+            }
+        }
+
+        Ok(RuleTable {
+            n_states,
+            n_type,
+            symmetry,
+            neighbourhood: neighbour_order(n_type).iter().map(|(x, y)| Point::new(*x, *y)).collect(),
+            transitions,
+            by_state,
+        })
+    }
+
+
+    pub fn n_type(&self) -> NType {
+        self.n_type
+    }
+
+    /// The last symmetry declared in the table
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+}
+
+impl Rule for RuleTable {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        Some(self.n_states)
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+        let Some(indices) = self.by_state.get(state.id() as usize) else {
+            return state;
+        };
+        indices
+            .iter()
+            .map(|i| &self.transitions[*i])
+            .find(|t| t.matches(neighbours))
+            .map_or(state, |t| State::new(t.output))
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+fn neighbour_order(n_type: NType) -> &'static [(i32, i32)] {
+    match n_type {
+        NType::VonNeumann => &VON_NEUMANN,
+        NType::Hexagonal => &HEXAGONAL,
+        _ => &MOORE,
+    }
+}
+
+/// Split a transition line into its entries, either comma separated or one
+/// digit per entry
+fn split_entries(line: &str) -> Vec<&str> {
+    if !line.contains(',') && !line.contains('{') {
+        return line.char_indices().map(|(i, c)| &line[i..i + c.len_utf8()]).collect();
+    }
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                entries.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(line[start..].trim());
+    entries
+}
+
+fn parse_state(s: &str, n_states: usize) -> Result<u8, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n < n_states => Ok(n as u8),
+        Ok(n) => Err(format!("state {} is not below n_states {}", n, n_states)),
+        Err(_) => Err(format!("invalid state '{}'", s)),
+    }
+}
+
+/// Parse `{a,1,2}` where each item is a state or a previously declared
+/// variable
+fn parse_set(s: &str, vars: &HashMap<String, Vec<u8>>, n_states: usize) -> Result<Vec<u8>, String> {
+    let inner = s
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or_else(|| format!("expected '{{...}}' but found '{}'", s))?;
+    let mut states = Vec::new();
+    for item in inner.split(',').map(str::trim) {
+        match vars.get(item) {
+            Some(v) => states.extend(v),
+            None => states.push(parse_state(item, n_states)?),
+        }
+    }
+    Ok(states)
+}
+
+fn parse_entry(s: &str, vars: &HashMap<String, Vec<u8>>, n_states: usize) -> Result<Entry, String> {
+    if s.starts_with('{') {
+        Ok(Entry::States(parse_set(s, vars, n_states)?))
+    } else if vars.contains_key(s) {
+        Ok(Entry::Var(s.to_string()))
+    } else if s.chars().all(|c| c.is_ascii_digit()) && !s.is_empty() {
+        Ok(Entry::States(vec![parse_state(s, n_states)?]))
+    } else {
+        Err(format!("undeclared variable '{}'", s))
+    }
+}
+
+/// Substitute every combination of values of the bound variables (those used
+/// more than once) into the transition and add all of its symmetric variants
+fn expand(
+    entries: &[Entry],
+    vars: &HashMap<String, Vec<u8>>,
+    symmetry: Symmetry,
+    transitions: &mut Vec<TableTransition>,
+) -> Result<(), String> {
+    let mut bound: Vec<&str> = Vec::new();
+    for (i, e) in entries.iter().enumerate() {
+        if let Entry::Var(name) = e {
+            let uses = entries.iter().filter(|o| matches!(o, Entry::Var(n) if n == name)).count();
+            if uses > 1 && !bound.contains(&name.as_str()) {
+                bound.push(name);
+            } else if uses == 1 && i == entries.len() - 1 {
+                return Err(format!("output variable '{}' is not used as an input", name));
+            }
+        }
+    }
+
+    let n_neighbours = entries.len() - 2;
+    let perms = symmetry.permutations(n_neighbours);
+    let mut seen = std::collections::HashSet::new();
+    let mut choice = vec![0; bound.len()];
+    loop {
+        let value = |name: &str| bound.iter().position(|b| *b == name).map(|i| vars[name][choice[i]]);
+        let mask = |e: &Entry| match e {
+            Entry::Var(name) => match value(name) {
+                Some(v) => StateMask::from_states(&[v]),
+                None => StateMask::from_states(&vars[name]),
+            },
+            Entry::States(states) => StateMask::from_states(states),
+        };
+        let output = match &entries[entries.len() - 1] {
+            Entry::Var(name) => value(name).unwrap_or_default(),
+            Entry::States(s) if s.len() == 1 => s[0],
+            Entry::States(_) => return Err("output must be a single state".into()),
+        };
+        let neighbours: Vec<StateMask> = entries[1..=n_neighbours].iter().map(mask).collect();
+        for perm in perms.iter() {
+            let t = TableTransition {
+                state: mask(&entries[0]),
+                neighbours: perm.iter().map(|p| neighbours[*p]).collect(),
+                output,
+                permute: symmetry == Symmetry::Permute,
+            };
+            if seen.insert(t.clone()) {
+                transitions.push(t);
+            }
+        }
+
+        // advance to the next combination of bound variable values
+        let mut i = 0;
+        while i < bound.len() {
+            choice[i] += 1;
+            if choice[i] < vars[bound[i]].len() {
+                break;
+            }
+            choice[i] = 0;
+            i += 1;
+        }
+        if i == bound.len() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::Automaton2D;
+    use crate::rule::next_ids;
+
+    const LIFE: &str = "@RULE Life
+@TABLE
+n_states:2
+neighborhood:Moore
+symmetries:permute
+var a={0,1}
+var b={a}
+var c={a}
+var d={a}
+var e={a}
+var f={a}
+var g={a}
+var h={a}
+var i={a}
+# birth
+0,1,1,1,0,0,0,0,0,1
+# survival
+1,1,1,0,0,0,0,0,0,1
+1,1,1,1,0,0,0,0,0,1
+1,a,b,c,d,e,f,g,h,0
+";
+
+    #[test]
+    fn life_blinker_test() {
+        let table = RuleTable::from_rule_file(LIFE).unwrap();
+        let mut automaton = Automaton2D::new(5, 5, table);
+        let blinker: Vec<_> = (1..4).map(|x| (Point::new(x, 2), State::new(1))).collect();
+        automaton.set_seed(&blinker).unwrap();
+        automaton.step();
+        let live: Vec<_> = automaton.grid().iter().filter(|(_, s)| s.id() == 1).map(|(p, _)| p).collect();
+        assert_eq!(live, vec![Point::new(2, 1), Point::new(2, 2), Point::new(2, 3)]);
+        automaton.step();
+        let live: Vec<_> = automaton.grid().iter().filter(|(_, s)| s.id() == 1).map(|(p, _)| p).collect();
+        assert_eq!(live, blinker.iter().map(|(p, _)| *p).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rotate_symmetry_test() {
+        let table = RuleTable::parse("n_states:3\nneighborhood:Moore\nsymmetries:rotate4\n0,1,2,0,0,0,0,0,0,2\n").unwrap();
+        assert_eq!(next_ids(&table, 0, &[1, 2, 0, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(next_ids(&table, 0, &[0, 0, 1, 2, 0, 0, 0, 0]), 2);
+        assert_eq!(next_ids(&table, 0, &[0, 1, 2, 0, 0, 0, 0, 0]), 0);
+
+        let table = RuleTable::parse("n_states:3\nneighborhood:Moore\nsymmetries:rotate8reflect\n0,1,2,0,0,0,0,0,0,2\n").unwrap();
+        assert_eq!(next_ids(&table, 0, &[0, 1, 2, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(next_ids(&table, 0, &[2, 1, 0, 0, 0, 0, 0, 0]), 2);
+    }
+
+    #[test]
+    fn hexagonal_symmetry_test() {
+        let table = RuleTable::parse("n_states:2\nneighborhood:hexagonal\nsymmetries:rotate3\n0,1,1,0,0,0,0,1\n").unwrap();
+        assert_eq!(next_ids(&table, 0, &[0, 0, 1, 1, 0, 0]), 1);
+        assert_eq!(next_ids(&table, 0, &[0, 1, 1, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn bound_variable_test() {
+        let table = RuleTable::parse(
+            "n_states:3\nneighborhood:vonNeumann\nsymmetries:none\nvar a={1,2}\n0,a,a,0,0,a\n",
+        )
+        .unwrap();
+        assert_eq!(next_ids(&table, 0, &[2, 2, 0, 0]), 2);
+        assert_eq!(next_ids(&table, 0, &[1, 1, 0, 0]), 1);
+        assert_eq!(next_ids(&table, 0, &[1, 2, 0, 0]), 0);
+    }
+
+    #[test]
+    fn compact_permute_test() {
+        let table = RuleTable::parse("n_states:3\nneighborhood:vonNeumann\nsymmetries:permute\n021002\n").unwrap();
+        assert_eq!(next_ids(&table, 0, &[0, 0, 2, 1]), 2);
+        assert_eq!(next_ids(&table, 0, &[2, 2, 0, 0]), 0);
+    }
+
+    #[test]
+    fn error_line_test() {
+        let err = RuleTable::from_rule_file("@RULE Bad\n@TABLE\nn_states:2\nneighborhood:Moore\n0,1,2\n").unwrap_err();
+        assert_eq!(err.line(), 5);
+        let err = RuleTable::parse("n_states:2\nneighborhood:vonNeumann\nsymmetries:rotate8\n").unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert!(RuleTable::parse("n_states:2\nneighborhood:Moore\n0,1,1,1,0,0,0,0,0,x\n").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::errors::LoadError;
+use crate::golly;
+use crate::grid::point::Point;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::State;
+
+/// Order the tree reads the neighbours of a Moore rule in, the cell itself is
+/// read last
+const MOORE: [(i32, i32); 8] = [(-1, -1), (1, -1), (-1, 1), (1, 1), (0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// Order the tree reads the neighbours of a von Neumann rule in, the cell
+/// itself is read last
+const VON_NEUMANN: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// A rule loaded from the `@TREE` section of a Golly `.rule` file.
+///
+/// The tree is a decision diagram that reads one neighbour per level (nw, ne,
+/// sw, se, n, w, e, s for Moore or n, w, e, s for von Neumann) and finally the
+/// cell itself, whose leaf gives the next state. The nodes are compiled into
+/// one flat array where each entry is either the offset of the child node or,
+/// on the last level, the next state.
+///
+/// # Examples
+///
+/// ```
+/// use ca::golly::rule_tree::RuleTree;
+/// use ca::rule::Rule;
+/// use ca::state::State;
+///
+/// // a cell takes the state of its western neighbour
+/// let tree = RuleTree::parse("
+/// num_states=2
+/// num_neighbors=4
+/// num_nodes=8
+/// 1 0 0
+/// 1 1 1
+/// 2 0 0
+/// 2 1 1
+/// 3 2 2
+/// 3 3 3
+/// 4 4 5
+/// 5 6 6
+/// ").unwrap();
+/// let [off, on] = [State::new(0), State::new(1)];
+/// assert_eq!(tree.next_state(off, &[off, on, off, off]), on);
+/// assert_eq!(tree.next_state(on, &[on, off, on, on]), off);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuleTree {
+    n_states: usize,
+    neighbourhood: Vec<Point<i32>>,
+    nodes: Vec<u32>,
+    root: u32,
+}
+
+impl RuleTree {
+    /// Parse the contents of a `.tree` file or the body of a `@TREE`
+    /// section
+    pub fn parse(tree: &str) -> Result<RuleTree, LoadError> {
+        Self::parse_from_line(tree, 1)
+    }
+
+    /// Parse the `@TREE` section of a `.rule` file
+    pub fn from_rule_file(rule_file: &str) -> Result<RuleTree, LoadError> {
+        let (first_line, tree) =
+            golly::section(rule_file, "@TREE").ok_or_else(|| LoadError::new(1, "no @TREE section"))?;
+        Self::parse_from_line(tree, first_line)
+    }
+
+    fn parse_from_line(tree: &str, first_line: usize) -> Result<RuleTree, LoadError> {
+        let mut n_states = None;
+        let mut n_neighbours = None;
+        let mut n_nodes = None;
+        // level and offset into `nodes` of every node read so far
+        let mut offsets: Vec<(usize, u32)> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut last_line = first_line;
+
+        for (i, line) in tree.lines().enumerate() {
+            let line_no = first_line + i;
+            let err = |reason: String| LoadError::new(line_no, reason);
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            last_line = line_no;
+
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| err(format!("invalid value for '{}'", key.trim())))?;
+                match key.trim() {
+                    "num_states" if (2..=256).contains(&value) => n_states = Some(value),
+                    "num_neighbors" | "num_neighbours" if value == 4 || value == 8 => n_neighbours = Some(value),
+                    "num_nodes" if value > 0 => n_nodes = Some(value),
+                    key => return Err(err(format!("invalid {} '{}'", key, value))),
+                }
+                continue;
+            }
+
+            let (Some(n_states), Some(_), Some(n_nodes)) = (n_states, n_neighbours, n_nodes) else {
+                return Err(err("num_states, num_neighbors and num_nodes must be declared first".into()));
+            };
+            if offsets.len() == n_nodes {
+                return Err(err(format!("more than {} nodes", n_nodes)));
+            }
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<usize>().map_err(|_| err(format!("invalid number '{}'", v))))
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.len() != n_states + 1 {
+                return Err(err(format!("expected {} values but found {}", n_states + 1, values.len())));
+            }
+
+            let level = values[0];
+            if level == 0 {
+                return Err(err("levels start at 1".into()));
+            }
+            let offset = nodes.len() as u32;
+            for v in &values[1..] {
+                if level == 1 {
+                    if *v >= n_states {
+                        return Err(err(format!("state {} is not below num_states {}", v, n_states)));
+                    }
+                    nodes.push(*v as u32);
+                } else {
+                    match offsets.get(*v) {
+                        Some((l, o)) if *l == level - 1 => nodes.push(*o),
+                        Some(_) => return Err(err(format!("node {} is not on level {}", v, level - 1))),
+                        None => return Err(err(format!("node {} has not been declared", v))),
+                    }
+                }
+            }
+            offsets.push((level, offset));
+        }
+
+        let err = |reason: &str| LoadError::new(last_line, reason);
+        let n_states = n_states.ok_or_else(|| err("missing num_states"))?;
+        let n_neighbours = n_neighbours.ok_or_else(|| err("missing num_neighbors"))?;
+        let n_nodes = n_nodes.ok_or_else(|| err("missing num_nodes"))?;
+        if offsets.len() != n_nodes {
+            return Err(err("fewer nodes than num_nodes"));
+        }
+        let (level, root) = offsets[n_nodes - 1];
+        if level != n_neighbours + 1 {
+            return Err(err("the last node must be the root on level num_neighbors + 1"));
+        }
+
+        let order: &[(i32, i32)] = if n_neighbours == 4 { &VON_NEUMANN } else { &MOORE };
+        Ok(RuleTree {
+            n_states,
+            neighbourhood: order.iter().map(|(x, y)| Point::new(*x, *y)).collect(),
+            nodes,
+            root,
+        })
+    }
+
+}
+
+impl Rule for RuleTree {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        Some(self.n_states)
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+        let n_states = self.n_states;
+        if state.id() as usize >= n_states || neighbours.iter().any(|n| n.id() as usize >= n_states) {
+            return state;
+        }
+        let node = neighbours.iter().fold(self.root, |node, n| self.nodes[(node + n.id() as u32) as usize]);
+        State::new(self.nodes[(node + state.id() as u32) as usize] as u8)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::Automaton2D;
+    use crate::rule::next_ids;
+    use std::collections::HashMap;
+
+    /// Write out the tree of `f`, which is given the variables in the order
+    /// the tree reads them
+    fn tree_of(n_states: usize, n_neighbours: usize, f: &dyn Fn(&[u8]) -> u8) -> String {
+        fn node(
+            level: usize,
+            vars: &mut Vec<u8>,
+            n_states: usize,
+            f: &dyn Fn(&[u8]) -> u8,
+            lines: &mut Vec<String>,
+            seen: &mut HashMap<String, usize>,
+        ) -> usize {
+            let mut line = level.to_string();
+            for v in 0..n_states as u8 {
+                vars.push(v);
+                let child = match level {
+                    1 => f(vars) as usize,
+                    _ => node(level - 1, vars, n_states, f, lines, seen),
+                };
+                vars.pop();
+                line += &format!(" {}", child);
+            }
+            *seen.entry(line.clone()).or_insert_with(|| {
+                lines.push(line);
+                lines.len() - 1
+            })
+        }
+
+        let mut lines = Vec::new();
+        node(n_neighbours + 1, &mut Vec::new(), n_states, f, &mut lines, &mut HashMap::new());
+        format!(
+            "num_states={}\nnum_neighbors={}\nnum_nodes={}\n{}\n",
+            n_states,
+            n_neighbours,
+            lines.len(),
+            lines.join("\n")
+        )
+    }
+
+    #[test]
+    fn life_blinker_test() {
+        let life = tree_of(2, 8, &|v| {
+            let count: u8 = v[..8].iter().sum();
+            (count == 3 || (count == 2 && v[8] == 1)) as u8
+        });
+        let tree = RuleTree::from_rule_file(&format!("@RULE Life\n@TREE\n{}", life)).unwrap();
+        let mut automaton = Automaton2D::new(5, 5, tree);
+        let blinker: Vec<_> = (1..4).map(|x| (Point::new(x, 2), State::new(1))).collect();
+        automaton.set_seed(&blinker).unwrap();
+        automaton.step();
+        let live: Vec<_> = automaton.grid().iter().filter(|(_, s)| s.id() == 1).map(|(p, _)| p).collect();
+        assert_eq!(live, vec![Point::new(2, 1), Point::new(2, 2), Point::new(2, 3)]);
+    }
+
+    #[test]
+    fn neighbour_order_test() {
+        // next state is the state of the western neighbour, plus one if the
+        // cell itself is in state 1
+        let tree = RuleTree::parse(&tree_of(3, 4, &|v| (v[1] + (v[4] == 1) as u8) % 3)).unwrap();
+        assert_eq!(next_ids(&tree, 0, &[0, 2, 0, 0]), 2);
+        assert_eq!(next_ids(&tree, 1, &[1, 1, 2, 2]), 2);
+        assert_eq!(next_ids(&tree, 1, &[0, 2, 0, 0]), 0);
+        assert_eq!(tree.neighbourhood()[1], Point::new(-1, 0));
+    }
+
+    #[test]
+    fn invalid_tree_test() {
+        let err = RuleTree::parse("num_states=2\nnum_neighbors=4\nnum_nodes=2\n1 0 1\n2 0 1\n").unwrap_err();
+        assert_eq!(err.line(), 5);
+        let err = RuleTree::parse("num_states=2\nnum_neighbors=6\n").unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert!(RuleTree::parse("num_states=2\nnum_neighbors=4\nnum_nodes=1\n1 0 1\n").is_err());
+    }
+}
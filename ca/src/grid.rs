@@ -5,3 +5,9 @@ pub mod apoint;
 pub mod neighbourhood;
 
 pub mod grid_2d;
+
+pub mod dense_grid;
+
+pub mod layers;
+
+pub mod quadtree;
@@ -0,0 +1,159 @@
+use crate::grid::point::{IsPoint, Point};
+
+/// How cells beyond the edge of a grid are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Boundary {
+    /// Cells outside of the grid are always in the default state
+    #[default]
+    Fixed,
+    /// The grid wraps around at its edges
+    Toroidal,
+}
+
+/// A fixed size 2 dimensional grid storing one value for every cell.
+///
+/// `x` is the column and `y` is the row, with the origin in the top left
+/// corner, so north is `(0, -1)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenseGrid<T> {
+    width: usize,
+    height: usize,
+    boundary: Boundary,
+    cells: Vec<T>,
+}
+
+impl<T> DenseGrid<T>
+where
+    T: Clone + Default, {
+    /// Return a new grid with every cell in the default state and a
+    /// [`Boundary::Fixed`] boundary
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::grid::dense_grid::DenseGrid;
+    /// let grid: DenseGrid<u8> = DenseGrid::new(4, 3);
+    /// assert_eq!(grid.cells().len(), 12);
+    /// ```
+    pub fn new(width: usize, height: usize) -> DenseGrid<T> {
+        Self::with_boundary(width, height, Boundary::Fixed)
+    }
+
+    /// Return a new grid with every cell in the default state
+    pub fn with_boundary(width: usize, height: usize, boundary: Boundary) -> DenseGrid<T> {
+        DenseGrid {
+            width,
+            height,
+            boundary,
+            cells: vec![T::default(); width * height],
+        }
+    }
+
+    /// Set every cell back to the default state
+    pub fn clear(&mut self) {
+        self.fill(T::default());
+    }
+}
+
+impl<T> DenseGrid<T>
+where
+    T: Clone, {
+    /// Set every cell to `v`
+    pub fn fill(&mut self, v: T) {
+        self.cells.iter_mut().for_each(|c| *c = v.clone());
+    }
+}
+
+impl<T> DenseGrid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
+    /// All cells in row major order
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    /// All cells in row major order
+    pub fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.cells
+    }
+
+    /// Return the index into [`DenseGrid::cells`] of a point, wrapping it
+    /// around the edges on a toroidal grid. `None` if the point is outside of
+    /// a fixed grid
+    pub fn index(&self, p: Point<i32>) -> Option<usize> {
+        let (w, h) = (self.width as i32, self.height as i32);
+        if w == 0 || h == 0 {
+            return None;
+        }
+        let (x, y) = match self.boundary {
+            Boundary::Fixed => (p.x(), p.y()),
+            Boundary::Toroidal => (p.x().rem_euclid(w), p.y().rem_euclid(h)),
+        };
+        if x < 0 || y < 0 || x >= w || y >= h {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    /// Return the point of a cell index
+    pub fn point(&self, index: usize) -> Point<i32> {
+        Point::new((index % self.width) as i32, (index / self.width) as i32)
+    }
+
+    /// Get the value of a cell, `None` if the point is outside of the grid
+    pub fn get(&self, p: Point<i32>) -> Option<&T> {
+        self.index(p).map(|i| &self.cells[i])
+    }
+
+    /// Set the value of a cell, returning the old value. `None` if the point
+    /// is outside of the grid
+    pub fn set(&mut self, p: Point<i32>, v: T) -> Option<T> {
+        let i = self.index(p)?;
+        Some(std::mem::replace(&mut self.cells[i], v))
+    }
+
+    /// Iterate over every cell along with its point, in row major order
+    pub fn iter(&self) -> impl Iterator<Item = (Point<i32>, &T)> {
+        self.cells.iter().enumerate().map(|(i, c)| (self.point(i), c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_boundary_test() {
+        let mut grid: DenseGrid<u8> = DenseGrid::new(3, 2);
+        assert_eq!(grid.set(Point::new(2, 1), 4), Some(0));
+        assert_eq!(grid.get(Point::new(2, 1)), Some(&4));
+        assert_eq!(grid.get(Point::new(3, 1)), None);
+        assert_eq!(grid.get(Point::new(-1, 0)), None);
+        assert_eq!(grid.cells()[5], 4);
+    }
+
+    #[test]
+    fn toroidal_boundary_test() {
+        let mut grid: DenseGrid<u8> = DenseGrid::with_boundary(3, 2, Boundary::Toroidal);
+        grid.set(Point::new(0, 0), 1);
+        assert_eq!(grid.get(Point::new(3, 2)), Some(&1));
+        assert_eq!(grid.get(Point::new(-3, -2)), Some(&1));
+        assert_eq!(grid.point(4), Point::new(1, 1));
+    }
+}
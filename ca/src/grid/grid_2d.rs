@@ -39,6 +39,15 @@ where
 
 }
 
+impl<'a, T, U> Default for Grid2D<'a, T, U>
+where
+    T: IsPoint<U>,
+    U: Copy + Ord + PartialEq + Add<Output = U> + Mul<Output = U>, {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // #[test]
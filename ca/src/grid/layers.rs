@@ -0,0 +1,280 @@
+use std::any::{type_name, Any};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::errors::CaError;
+use crate::grid::dense_grid::{Boundary, DenseGrid};
+
+type AnyGrid = Box<dyn Any + Send + Sync>;
+
+/// Source of the tags telling apart the handles of different [`Layers`]
+static NEXT_OWNER: AtomicU64 = AtomicU64::new(0);
+
+/// Typed handle to a layer of a [`Layers`], returned when the layer is added
+/// or looked up by name. Handles are valid for the `Layers` they came from and
+/// its clones
+pub struct LayerId<T> {
+    owner: u64,
+    index: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> LayerId<T> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Clone for LayerId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for LayerId<T> {}
+
+impl<T> PartialEq for LayerId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.owner, self.index) == (other.owner, other.index)
+    }
+}
+
+impl<T> Debug for LayerId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LayerId<{}>({})", type_name::<T>(), self.index)
+    }
+}
+
+/// Type erased grid along with the operations which need the type of its cells
+struct Layer {
+    name: String,
+    grid: AnyGrid,
+    clone: fn(&AnyGrid) -> AnyGrid,
+    copy: fn(&AnyGrid, &mut AnyGrid),
+    set_boundary: fn(&mut AnyGrid, Boundary),
+}
+
+fn typed<T: 'static>(grid: &AnyGrid) -> &DenseGrid<T> {
+    grid.downcast_ref().expect("layer holds another type")
+}
+
+fn typed_mut<T: 'static>(grid: &mut AnyGrid) -> &mut DenseGrid<T> {
+    grid.downcast_mut().expect("layer holds another type")
+}
+
+/// Named grids of the same size, each storing its own type of cell, e.g. the
+/// concentration of each chemical in a reaction-diffusion model
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::layers::Layers;
+/// use ca::grid::point::Point;
+/// use ca::state::State;
+///
+/// let mut layers = Layers::new(8, 8);
+/// let alive = layers.add::<State>("alive").unwrap();
+/// let heat = layers.add::<f64>("heat").unwrap();
+/// layers.get_mut(heat).set(Point::new(1, 1), 0.5);
+/// assert_eq!(layers.id::<f64>("heat").unwrap(), heat);
+/// assert!(layers.id::<State>("heat").is_err());
+/// assert_eq!(layers.get(alive).get(Point::new(1, 1)), Some(&State::new(0)));
+/// ```
+pub struct Layers {
+    owner: u64,
+    width: usize,
+    height: usize,
+    boundary: Boundary,
+    layers: Vec<Layer>,
+}
+
+impl Layers {
+    /// Return a new set of `width` by `height` layers with a
+    /// [`Boundary::Fixed`] boundary and no layers
+    pub fn new(width: usize, height: usize) -> Layers {
+        Layers {
+            owner: NEXT_OWNER.fetch_add(1, Ordering::Relaxed),
+            width,
+            height,
+            boundary: Boundary::Fixed,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Add a layer with every cell in the default state. Fails if there is
+    /// already a layer called `name`
+    pub fn add<T>(&mut self, name: &str) -> Result<LayerId<T>, CaError>
+    where
+        T: Clone + Default + Send + Sync + 'static, {
+        if self.layers.iter().any(|l| l.name == name) {
+            return Err(CaError::InvalidLayer(format!("{} already exists", name)));
+        }
+        let grid: DenseGrid<T> = DenseGrid::with_boundary(self.width, self.height, self.boundary);
+        self.layers.push(Layer {
+            name: name.to_string(),
+            grid: Box::new(grid),
+            clone: |g| Box::new(typed::<T>(g).clone()),
+            copy: |from, to| typed_mut::<T>(to).cells_mut().clone_from_slice(typed::<T>(from).cells()),
+            set_boundary: |g, b| typed_mut::<T>(g).set_boundary(b),
+        });
+        Ok(LayerId {
+            owner: self.owner,
+            index: self.layers.len() - 1,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Handle to the layer called `name`. Fails if there is no such layer or
+    /// it doesn't hold `T`
+    pub fn id<T: 'static>(&self, name: &str) -> Result<LayerId<T>, CaError> {
+        let index = self
+            .layers
+            .iter()
+            .position(|l| l.name == name)
+            .ok_or_else(|| CaError::InvalidLayer(format!("no layer called {}", name)))?;
+        if !self.layers[index].grid.is::<DenseGrid<T>>() {
+            return Err(CaError::InvalidLayer(format!("{} doesn't hold {}", name, type_name::<T>())));
+        }
+        Ok(LayerId {
+            owner: self.owner,
+            index,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Grid of a layer
+    ///
+    /// # Panics
+    ///
+    /// If `id` comes from a `Layers` which isn't this one or a clone of it
+    pub fn get<T: 'static>(&self, id: LayerId<T>) -> &DenseGrid<T> {
+        typed(&self.layers[self.check(id)].grid)
+    }
+
+    /// Mutable grid of a layer
+    ///
+    /// # Panics
+    ///
+    /// If `id` comes from a `Layers` which isn't this one or a clone of it
+    pub fn get_mut<T: 'static>(&mut self, id: LayerId<T>) -> &mut DenseGrid<T> {
+        let index = self.check(id);
+        typed_mut(&mut self.layers[index].grid)
+    }
+
+    /// Index of the layer `id` refers to
+    fn check<T>(&self, id: LayerId<T>) -> usize {
+        assert_eq!(id.owner, self.owner, "{:?} belongs to another Layers", id);
+        id.index
+    }
+
+    /// Names of the layers in the order they were added
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|l| l.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// Set the boundary of every layer, including those added later
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+        for layer in self.layers.iter_mut() {
+            (layer.set_boundary)(&mut layer.grid, boundary);
+        }
+    }
+
+    /// Copy every cell of `other`, which must have the same layers
+    pub(crate) fn copy_from(&mut self, other: &Layers) {
+        for (to, from) in self.layers.iter_mut().zip(&other.layers) {
+            (to.copy)(&from.grid, &mut to.grid);
+        }
+    }
+}
+
+impl Clone for Layers {
+    fn clone(&self) -> Self {
+        Layers {
+            owner: self.owner,
+            width: self.width,
+            height: self.height,
+            boundary: self.boundary,
+            layers: self
+                .layers
+                .iter()
+                .map(|l| Layer {
+                    name: l.name.clone(),
+                    grid: (l.clone)(&l.grid),
+                    clone: l.clone,
+                    copy: l.copy,
+                    set_boundary: l.set_boundary,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Debug for Layers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Layers")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("boundary", &self.boundary)
+            .field("layers", &self.names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+    use crate::state::State;
+
+    #[test]
+    fn layers_test() {
+        let mut layers = Layers::new(3, 2);
+        let a = layers.add::<State>("a").unwrap();
+        let b = layers.add::<(u8, f32)>("b").unwrap();
+        assert!(matches!(layers.add::<f64>("a"), Err(CaError::InvalidLayer(_))));
+        assert!(matches!(layers.id::<f64>("c"), Err(CaError::InvalidLayer(_))));
+        assert_eq!(layers.names().collect::<Vec<_>>(), vec!["a", "b"]);
+
+        layers.get_mut(b).set(Point::new(2, 1), (1, 0.5));
+        let mut copy = layers.clone();
+        assert_eq!(copy.get(b).get(Point::new(2, 1)), Some(&(1, 0.5)));
+        copy.get_mut(a).fill(State::new(2));
+        copy.copy_from(&layers);
+        assert_eq!(copy.get(a).cells(), layers.get(a).cells());
+
+        layers.set_boundary(Boundary::Toroidal);
+        assert_eq!(layers.get(b).get(Point::new(-1, -1)), Some(&(1, 0.5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "belongs to another Layers")]
+    fn foreign_id_test() {
+        let mut layers = Layers::new(3, 2);
+        let mut other = Layers::new(3, 2);
+        layers.add::<State>("a").unwrap();
+        let id = other.add::<f64>("a").unwrap();
+        layers.get(id);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::errors::CaError;
 use crate::grid::point::*;
 
 // TODO: Rewrite
@@ -5,16 +6,28 @@ use crate::grid::point::*;
 
 pub type Neighbours = Vec<Point<i32>>;
 
+/// Largest size of a distance neighbourhood, a Chebyshev neighbourhood of this
+/// size already has 16640 cells
+pub const MAX_DISTANCE: i32 = 64;
+
 /// Different 2Dimensional Neighbourhoods for a cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum NType {
     Moore,
     VonNeumann,
     ChebyshevDistance,
     ManhattanDistance,
+    /// Hexagonal grid emulated on a square one: the Moore neighbourhood
+    /// without the north east and south west cells
+    Hexagonal,
 }
 
 /// The 8 possible directions to move in a 2 dimensional grid
 /// North is above current cell
+#[allow(dead_code)]
+#[derive(Debug)]
 enum Direction {
     North,
     NorthEast,
@@ -41,13 +54,11 @@ impl Direction {
     }
 }
 
-pub struct MissingNeighbourhoodSize;
-
 pub fn neighbourhood_coords(
     n_type: NType,
     n_size: Option<i32>,
     loc: Point<i32>,
-) -> Result<Neighbours, MissingNeighbourhoodSize> {
+) -> Result<Neighbours, CaError> {
     let mut neighbours = Vec::new();
     relative_neighbourhood_coords(n_type, n_size)?;
     convert_relative_point_to_absolute(&mut neighbours, &loc);
@@ -59,33 +70,46 @@ pub fn neighbourhood_coords(
 pub fn relative_neighbourhood_coords(
     n_type: NType,
     n_size: Option<i32>,
-) -> Result<Neighbours, MissingNeighbourhoodSize> {
+) -> Result<Neighbours, CaError> {
     let mut neighbours: Neighbours = Vec::new();
     match n_type {
         NType::Moore => add_moore_neighbourhood_cells(&mut neighbours),
         NType::VonNeumann => add_von_neumann_neighbourhood_cells(&mut neighbours),
+        NType::Hexagonal => add_hexagonal_neighbourhood_cells(&mut neighbours),
         NType::ChebyshevDistance => {
-            if n_size.is_none() {
-                return Err(MissingNeighbourhoodSize);
-            }
-            add_chebyshev_distance_cells(&mut neighbours, n_size.unwrap());
+            add_chebyshev_distance_cells(&mut neighbours, distance_size(n_type, n_size)?);
         }
         NType::ManhattanDistance => {
-            if n_size.is_none() {
-                return Err(MissingNeighbourhoodSize);
-            }
-            add_chebyshev_distance_cells(&mut neighbours, n_size.unwrap());
+            add_manahattan_distance_cells(&mut neighbours, distance_size(n_type, n_size)?);
         }
     }
     Ok(neighbours)
 }
 
+/// Size of a distance neighbourhood, which must be given and between 1 and
+/// [`MAX_DISTANCE`]
+fn distance_size(n_type: NType, n_size: Option<i32>) -> Result<i32, CaError> {
+    match n_size {
+        Some(size @ 1..=MAX_DISTANCE) => Ok(size),
+        Some(size) => Err(CaError::InvalidNeighbourhood(format!(
+            "{:?} size {} is not between 1 and {}",
+            n_type, size, MAX_DISTANCE
+        ))),
+        None => Err(CaError::InvalidNeighbourhood(format!("{:?} needs a size", n_type))),
+    }
+}
+
 fn add_moore_neighbourhood_cells(neighbours: &mut Neighbours) {
     add_chebyshev_distance_cells(neighbours, 1)
 }
 
 fn add_von_neumann_neighbourhood_cells(neighbours: &mut Neighbours) {
-    add_manahattan_distance_cells(neighbours, 1)
+    add_adjacent_cells(neighbours, &Point::new(0, 0), 1)
+}
+
+fn add_hexagonal_neighbourhood_cells(neighbours: &mut Neighbours) {
+    add_moore_neighbourhood_cells(neighbours);
+    neighbours.retain(|n| *n != Point::new(1, -1) && *n != Point::new(-1, 1));
 }
 
 fn add_chebyshev_distance_cells(neighbours: &mut Neighbours, neighbourhood_size: i32) {
@@ -97,17 +121,14 @@ fn add_chebyshev_distance_cells(neighbours: &mut Neighbours, neighbourhood_size:
     }
 }
 
+/// Every cell with `|x| + |y| <= neighbourhood_size` other than the centre
 fn add_manahattan_distance_cells(neighbours: &mut Neighbours, neighbourhood_size: i32) {
-    add_adjacent_cells(neighbours, &Point::new(0, 0), 1);
-    let mut count = 0;
-    if neighbourhood_size == 1 {
-        return;
-    }
-    for _ in 1..neighbourhood_size {
-        for _ in count..neighbours.len() {
-            let curr = Point::new(neighbours[count].x(), neighbours[count].y());
-            add_adjacent_cells(neighbours, &curr, 1);
-            count += 1;
+    for x in -neighbourhood_size..=neighbourhood_size {
+        let width = neighbourhood_size - x.abs();
+        for y in -width..=width {
+            if (x, y) != (0, 0) {
+                neighbours.push(Point::new(x, y));
+            }
         }
     }
 }
@@ -144,17 +165,22 @@ fn add_all_cells_across(neighbours: &mut Neighbours, count: i32) {
     .ok();
 }
 
-pub struct InvalidDirection;
 fn add_cells_ptp(
     neighbours: &mut Neighbours,
     direction: Direction,
     start: &Point<i32>,
     finish: &Point<i32>,
-) -> Result<(), InvalidDirection> {
+) -> Result<(), CaError> {
     match direction {
-        Direction::East | Direction::West => Ok(add_cells_horizontally(neighbours, start, finish)),
-        Direction::North | Direction::South => Ok(add_cells_vertically(neighbours, start, finish)),
-        _ => Err(InvalidDirection),
+        Direction::East | Direction::West => {
+            add_cells_horizontally(neighbours, start, finish);
+            Ok(())
+        }
+        Direction::North | Direction::South => {
+            add_cells_vertically(neighbours, start, finish);
+            Ok(())
+        }
+        _ => Err(CaError::InvalidNeighbourhood(format!("cells can't be added towards {:?}", direction))),
     }
 }
 
@@ -186,9 +212,8 @@ fn add_adjacent_cells(n: &mut Neighbours, rel_cell: &Point<i32>, distance: i32)
     }
 }
 pub fn convert_relative_point_to_absolute(neighbours: &mut Neighbours, cell: &Point<i32>) {
-    // neighbours.iter_mut().map(|n| *n + *cell).for_each(drop);
-    for i in 0..neighbours.len() {
-        neighbours[i] = neighbours[i] + *cell;
+    for n in neighbours.iter_mut() {
+        *n = *n + *cell;
     }
 }
 
@@ -215,6 +240,7 @@ pub fn remove_self(neighbours: &mut Neighbours, loc: &Point<i32>) {
         i += 1;
     }
 }
+
 pub fn is_valid_point(point: &Point<i32>, max_dims: &Point<i32>) -> bool {
     point.x() < max_dims.x() && point.y() < max_dims.y() && point.x() >= 0 && point.y() >= 0
 }
@@ -225,15 +251,7 @@ mod tests {
     use super::*;
 
     fn print_neighbourhood(loc: Point<i32>, neighbours: Vec<Point<i32>>, size: i32) {
-        let mut grid = Vec::new();
-
-        for _ in 0..size {
-            let mut cur = Vec::new();
-            for _ in 0..size {
-                cur.push(" . ");
-            }
-            grid.push(cur);
-        }
+        let mut grid = vec![vec![" . "; size as usize]; size as usize];
 
         grid[loc.y() as usize][loc.x() as usize] = "\x1b[0;33m X \x1b[0m";
 
@@ -241,9 +259,9 @@ mod tests {
             grid[n.y() as usize][n.x() as usize] = "\x1b[0;91m X \x1b[0m";
         }
 
-        for i in 0..size as usize {
-            for j in 0..size as usize {
-                print!("{}", grid[i][j]);
+        for row in grid.iter() {
+            for cell in row.iter() {
+                print!("{}", cell);
             }
             println!();
         }
@@ -258,7 +276,40 @@ mod tests {
         convert_relative_point_to_absolute(&mut neighbours, &loc);
         remove_invalid(&mut neighbours, &Point::new(size, size));
         remove_self(&mut neighbours, &loc);
-        // print_neighbourhood(loc, neighbours, size as i32);
+        // print_neighbourhood(loc, neighbours, size);
+    }
+
+    #[test]
+    fn hexagonal_neighbourhood_test() {
+        let neighbours = relative_neighbourhood_coords(NType::Hexagonal, None).ok().unwrap();
+        assert_eq!(neighbours.len(), 6);
+        assert!(!neighbours.contains(&Point::new(1, -1)));
+        assert!(!neighbours.contains(&Point::new(-1, 1)));
+    }
+
+    #[test]
+    fn invalid_neighbourhood_test() {
+        let err = relative_neighbourhood_coords(NType::ChebyshevDistance, None).unwrap_err();
+        assert_eq!(err.to_string(), "invalid neighbourhood: ChebyshevDistance needs a size");
+        assert!(relative_neighbourhood_coords(NType::ManhattanDistance, Some(0)).is_err());
+        let err = relative_neighbourhood_coords(NType::ChebyshevDistance, Some(100_000)).unwrap_err();
+        assert_eq!(err.to_string(), "invalid neighbourhood: ChebyshevDistance size 100000 is not between 1 and 64");
+        assert!(add_cells_ptp(&mut Vec::new(), Direction::NorthEast, &Point::new(0, 0), &Point::new(1, 1)).is_err());
+    }
+
+    #[test]
+    fn manhattan_distance_neighbourhood_test() {
+        let neighbours = relative_neighbourhood_coords(NType::ManhattanDistance, Some(2)).ok().unwrap();
+        assert_eq!(neighbours.len(), 12);
+        assert!(!neighbours.contains(&Point::new(0, 0)));
+        assert!(!neighbours.contains(&Point::new(2, 1)));
+
+        // 2r(r + 1) cells, generated directly rather than by walking
+        let neighbours = relative_neighbourhood_coords(NType::ManhattanDistance, Some(64)).unwrap();
+        assert_eq!(neighbours.len(), 2 * 64 * 65);
+        assert!(neighbours.iter().all(|n| n.x().abs() + n.y().abs() <= 64));
+        let unique: std::collections::HashSet<_> = neighbours.iter().collect();
+        assert_eq!(unique.len(), neighbours.len());
     }
 
     #[test]
@@ -271,6 +322,6 @@ mod tests {
         convert_relative_point_to_absolute(&mut neighbours, &loc);
         remove_invalid(&mut neighbours, &Point::new(size, size));
         remove_self(&mut neighbours, &loc);
-        print_neighbourhood(loc, neighbours, size as i32);
+        print_neighbourhood(loc, neighbours, size);
     }
 }
@@ -10,7 +10,8 @@ where
 }
 
 /// Basic point struct containing only an x and y coordinate
-#[derive(Clone, Copy, Eq, PartialOrd, Ord, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialOrd, Ord, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point<T>
 where
     T: Copy + Ord + PartialEq + Add<Output = T> + Mul<Output = T>,
@@ -24,7 +25,7 @@ where
     T: Copy + Ord + PartialEq + Add<Output = T> + Mul<Output = T>,
 {
     pub fn new(x: T, y: T) -> Point<T> {
-         Point { x: x.clone(), y: y.clone() } }
+         Point { x, y } }
 
 }
 
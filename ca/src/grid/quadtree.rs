@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use crate::errors::CaError;
+use crate::grid::point::{IsPoint, Point};
+use crate::state::State;
+
+/// A node is either a single cell or four children of the level below, in
+/// the order north west, north east, south west, south east
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Node {
+    Leaf(State),
+    Branch { level: u32, children: [usize; 4] },
+}
+
+/// Square grid of `2^level` cells stored as a quadtree. Identical nodes are
+/// only stored once, so large repetitive or mostly empty patterns take
+/// little memory. Nodes replaced by [`QuadTree::set`] aren't freed
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::grid::quadtree::QuadTree;
+/// use ca::state::State;
+///
+/// let mut tree = QuadTree::new(10);
+/// assert_eq!(tree.size(), 1024);
+/// tree.set(Point::new(1000, 3), State::new(2)).unwrap();
+/// assert_eq!(tree.get(Point::new(1000, 3)), State::new(2));
+/// assert_eq!(tree.population(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    ids: HashMap<Node, usize>,
+    /// empty node of every level
+    empty: Vec<usize>,
+    root: usize,
+    level: u32,
+}
+
+impl QuadTree {
+    /// Return a new tree of `2^level` by `2^level` cells in state 0
+    pub fn new(level: u32) -> QuadTree {
+        let mut tree = QuadTree {
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            empty: Vec::new(),
+            root: 0,
+            level: 0,
+        };
+        tree.root = tree.empty(level);
+        tree.level = level;
+        tree
+    }
+
+    /// Return the smallest tree holding every cell, cells with a negative
+    /// coordinate are dropped
+    pub fn from_cells(cells: &[(Point<i32>, State)]) -> QuadTree {
+        let extent = cells
+            .iter()
+            .filter(|(p, _)| p.x() >= 0 && p.y() >= 0)
+            .map(|(p, _)| p.x().max(p.y()) as usize + 1)
+            .max()
+            .unwrap_or(1);
+        let mut tree = QuadTree::new(extent.next_power_of_two().trailing_zeros());
+        for (p, s) in cells {
+            let _ = tree.set(*p, *s);
+        }
+        tree
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Width and height of the grid
+    pub fn size(&self) -> usize {
+        1 << self.level
+    }
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    pub fn node(&self, id: usize) -> Node {
+        self.nodes[id]
+    }
+
+    /// Number of distinct nodes stored
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Return the id of `node`, adding it if it isn't stored yet
+    pub fn intern(&mut self, node: Node) -> usize {
+        if let Some(id) = self.ids.get(&node) {
+            return *id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.ids.insert(node, id);
+        // a node of dead cells is the empty node of its level however it was
+        // built, and the empty nodes of the levels below exist before it
+        let (level, empty) = match node {
+            Node::Leaf(s) => (0, s == State::default()),
+            Node::Branch { level, children } => {
+                (level, self.empty.get(level as usize - 1).is_some_and(|e| children.iter().all(|c| c == e)))
+            }
+        };
+        if empty && self.empty.len() == level as usize {
+            self.empty.push(id);
+        }
+        id
+    }
+
+    /// Id of the node of `2^level` cells in state 0
+    pub fn empty(&mut self, level: u32) -> usize {
+        while self.empty.len() <= level as usize {
+            let node = match self.empty.last() {
+                None => Node::Leaf(State::default()),
+                Some(child) => Node::Branch {
+                    level: self.empty.len() as u32,
+                    children: [*child; 4],
+                },
+            };
+            self.intern(node);
+        }
+        self.empty[level as usize]
+    }
+
+    /// Id of the node of `2^level` cells whose states are given by
+    /// `cell(x, y)`
+    pub fn build(&mut self, level: u32, cell: &impl Fn(usize, usize) -> State) -> usize {
+        self.build_at(level, 0, 0, cell)
+    }
+
+    fn build_at(&mut self, level: u32, x: usize, y: usize, cell: &impl Fn(usize, usize) -> State) -> usize {
+        if level == 0 {
+            return self.intern(Node::Leaf(cell(x, y)));
+        }
+        let half = 1 << (level - 1);
+        let children = [
+            self.build_at(level - 1, x, y, cell),
+            self.build_at(level - 1, x + half, y, cell),
+            self.build_at(level - 1, x, y + half, cell),
+            self.build_at(level - 1, x + half, y + half, cell),
+        ];
+        self.intern(Node::Branch { level, children })
+    }
+
+    /// Whether `id` is the node of its level in state 0
+    pub fn is_empty_node(&self, id: usize) -> bool {
+        self.empty.contains(&id)
+    }
+
+    /// Replace the whole grid with the node `root`, its level becomes the
+    /// level of the tree
+    pub fn set_root(&mut self, root: usize) {
+        self.level = match self.nodes[root] {
+            Node::Leaf(_) => 0,
+            Node::Branch { level, .. } => level,
+        };
+        self.root = root;
+    }
+
+    /// Double the size of the grid, keeping the cells in the north west
+    pub fn grow(&mut self) {
+        let empty = self.empty(self.level);
+        let root = self.intern(Node::Branch {
+            level: self.level + 1,
+            children: [self.root, empty, empty, empty],
+        });
+        self.set_root(root);
+    }
+
+    fn contains(&self, p: Point<i32>) -> bool {
+        let size = self.size() as i64;
+        (0..size).contains(&(p.x() as i64)) && (0..size).contains(&(p.y() as i64))
+    }
+
+    /// State of the cell at `p`, 0 outside of the grid
+    pub fn get(&self, p: Point<i32>) -> State {
+        if !self.contains(p) {
+            return State::default();
+        }
+        let (mut x, mut y, mut id) = (p.x() as usize, p.y() as usize, self.root);
+        loop {
+            match self.nodes[id] {
+                Node::Leaf(s) => return s,
+                Node::Branch { level, children } => {
+                    let half = 1 << (level - 1);
+                    id = children[(y >= half) as usize * 2 + (x >= half) as usize];
+                    x %= half;
+                    y %= half;
+                }
+            }
+        }
+    }
+
+    /// Set the cell at `p`, which must be inside of the grid
+    pub fn set(&mut self, p: Point<i32>, state: State) -> Result<(), CaError> {
+        if !self.contains(p) {
+            return Err(CaError::OutOfBounds {
+                point: p,
+                width: self.size(),
+                height: self.size(),
+            });
+        }
+        self.root = self.set_in(self.root, p.x() as usize, p.y() as usize, state);
+        Ok(())
+    }
+
+    fn set_in(&mut self, id: usize, x: usize, y: usize, state: State) -> usize {
+        match self.nodes[id] {
+            Node::Leaf(_) => self.intern(Node::Leaf(state)),
+            Node::Branch { level, mut children } => {
+                let half = 1 << (level - 1);
+                let q = (y >= half) as usize * 2 + (x >= half) as usize;
+                children[q] = self.set_in(children[q], x % half, y % half, state);
+                self.intern(Node::Branch { level, children })
+            }
+        }
+    }
+
+    /// Cells not in state 0, skipping empty nodes. Fails if a cell is beyond
+    /// the `i32` coordinates of points, which only trees above level 31 hold
+    pub fn cells(&self) -> Result<Vec<(Point<i32>, State)>, CaError> {
+        narrow(self.live(), Point::new(0, 0))
+    }
+
+    /// Cells not in state 0 relative to the north west corner of their
+    /// bounding box, so cells deep in trees of any level can be read. Fails
+    /// if the bounding box is larger than `i32` coordinates reach
+    pub fn bounded_cells(&self) -> Result<Vec<(Point<i32>, State)>, CaError> {
+        let cells = self.live();
+        let min_x = cells.iter().map(|(p, _)| p.x()).min().unwrap_or(0);
+        let min_y = cells.iter().map(|(p, _)| p.y()).min().unwrap_or(0);
+        narrow(cells, Point::new(min_x, min_y))
+    }
+
+    fn live(&self) -> Vec<(Point<i64>, State)> {
+        let mut cells = Vec::new();
+        self.collect(self.root, Point::new(0, 0), &mut cells);
+        cells
+    }
+
+    /// Push the cells of node `id` whose north west corner is at `origin`,
+    /// in i64 coordinates which hold trees up to level 62
+    fn collect(&self, id: usize, origin: Point<i64>, cells: &mut Vec<(Point<i64>, State)>) {
+        if self.is_empty_node(id) {
+            return;
+        }
+        match self.nodes[id] {
+            Node::Leaf(s) => cells.push((origin, s)),
+            Node::Branch { level, children } => {
+                let half = 1i64 << (level - 1);
+                for (q, child) in children.iter().enumerate() {
+                    let offset = Point::new(half * (q % 2) as i64, half * (q / 2) as i64);
+                    self.collect(*child, origin + offset, cells);
+                }
+            }
+        }
+    }
+
+    /// Number of cells not in state 0
+    pub fn population(&self) -> u64 {
+        let mut counts = HashMap::new();
+        self.count(self.root, &mut counts)
+    }
+
+    fn count(&self, id: usize, counts: &mut HashMap<usize, u64>) -> u64 {
+        if let Some(n) = counts.get(&id) {
+            return *n;
+        }
+        let n = match self.nodes[id] {
+            Node::Leaf(s) => (s.id() != 0) as u64,
+            Node::Branch { children, .. } => children.iter().map(|c| self.count(*c, counts)).sum(),
+        };
+        counts.insert(id, n);
+        n
+    }
+}
+
+/// Cells at their coordinates relative to `origin`, which must be points
+fn narrow(cells: Vec<(Point<i64>, State)>, origin: Point<i64>) -> Result<Vec<(Point<i32>, State)>, CaError> {
+    cells
+        .into_iter()
+        .map(|(p, s)| match (i32::try_from(p.x() - origin.x()), i32::try_from(p.y() - origin.y())) {
+            (Ok(x), Ok(y)) => Ok((Point::new(x, y), s)),
+            _ => Err(CaError::InvalidPattern(format!("cell ({}, {}) is beyond i32 coordinates", p.x(), p.y()))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quadtree_test() {
+        let mut tree = QuadTree::new(4);
+        for x in (0..16).step_by(4) {
+            for y in (0..16).step_by(4) {
+                tree.set(Point::new(x, y), State::new(1)).unwrap();
+            }
+        }
+        assert_eq!(tree.population(), 16);
+        // every 4x4 quadrant is the same node
+        let Node::Branch { children, .. } = tree.node(tree.root()) else { panic!() };
+        assert!(children.iter().all(|c| *c == children[0]));
+        assert!(tree.set(Point::new(16, 0), State::new(1)).is_err());
+
+        tree.grow();
+        assert_eq!(tree.size(), 32);
+        assert_eq!(tree.get(Point::new(12, 12)), State::new(1));
+        assert_eq!(tree.get(Point::new(13, 12)), State::new(0));
+        assert_eq!(tree.cells().unwrap().len(), 16);
+
+        let cells = tree.cells().unwrap();
+        let copy = QuadTree::from_cells(&cells);
+        assert_eq!((copy.level(), copy.cells().unwrap()), (4, cells));
+        let mut built = QuadTree::new(0);
+        let root = built.build(4, &|x, y| State::new((x % 4 == 0 && y % 4 == 0) as u8));
+        built.set_root(root);
+        assert_eq!(built.cells().unwrap(), copy.cells().unwrap());
+    }
+
+    #[test]
+    fn deep_tree_test() {
+        let mut tree = QuadTree::new(40);
+        assert_eq!(tree.cells().unwrap(), []);
+        tree.set(Point::new(5, 7), State::new(1)).unwrap();
+        assert_eq!(tree.cells().unwrap(), [(Point::new(5, 7), State::new(1))]);
+
+        // the south east corner is beyond i32 coordinates
+        let mut id = tree.intern(Node::Leaf(State::new(2)));
+        for level in 1..=40 {
+            let empty = tree.empty(level - 1);
+            id = tree.intern(Node::Branch { level, children: [empty, empty, empty, id] });
+        }
+        tree.set_root(id);
+        assert!(matches!(tree.cells(), Err(CaError::InvalidPattern(_))));
+        assert_eq!(tree.bounded_cells().unwrap(), [(Point::new(0, 0), State::new(2))]);
+    }
+}
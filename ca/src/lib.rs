@@ -23,9 +23,15 @@
 //!     - [ ] Documentation
 
 pub mod automaton;
-mod errors;
+pub mod config;
+pub mod continuous;
+pub mod errors;
+pub mod export;
+pub mod golly;
 pub mod grid;
 pub mod parse;
+pub mod pattern;
+pub mod rng;
 pub mod rule;
 pub mod state;
 
@@ -1,57 +1,719 @@
-use regex::Regex;
+//! # CA description language
+//!
+//! A cellular automaton is described by a name, its states, one or more
+//! neighbourhoods and the transitions between states. Whitespace and line
+//! breaks are not significant and `#` starts a comment running to the end of
+//! the line. States and neighbourhoods must be declared before they are used.
+//!
+//! ```text
+//! name: "Brian's Brain"
+//!
+//! states:
+//!     off = 0,
+//!     on = 1 (colour = "#ffffff", glyph = "#"),
+//!     dying = 2 (colour = "#3050ff", glyph = "+", description = "refractory")
+//!
+//! neighbourhood: moore
+//! neighbourhood far: chebyshev 2
+//!
+//! transitions:
+//!     off -> on when count(on) == 2
+//!     on -> dying
+//!     dying -> off when count(on | dying, far) < 12 or cell(0, -1) == on
+//! ```
+//!
+//! ## Sections
+//!
+//! - `name: <name>` - a quoted string or a single word
+//! - `states: <state> = <value>, ...` - values are the ids of the states
+//!   stored in the grid, from 0 to 255. A state can be followed by
+//!   `(colour = "#rrggbb", glyph = "<char>", description = "<text>")`, in any
+//!   order and all optional, to set how it is displayed
+//! - `neighbourhood [<name>]: <type>` - one of `moore`, `von_neumann`,
+//!   `hexagonal`, `chebyshev <radius>` or `manhattan <radius>`. The first
+//!   neighbourhood declared is the default one
+//! - `transitions: <from> -> <to> [when <condition>] ...` - the first
+//!   transition out of a cell's state whose condition holds gives its next
+//!   state, a cell with no such transition keeps its state
+//!
+//! ## Conditions
+//!
+//! Conditions compare values with `==`, `!=`, `<`, `<=`, `>` or `>=`, test if
+//! a value is in a range with `in 2..4` or `in 2..=3`, and are combined with
+//! `not`, `and`, `or` and parentheses. Values are
+//!
+//! - `count(<state> | ..., [<neighbourhood>])` - the number of neighbours in
+//!   any of the states
+//! - `sum([<neighbourhood>])` - the sum of the values of the neighbours
+//! - `cell(<x>, <y>)` - the value of the cell at an offset, north is `(0, -1)`
+//! - a number, or a state name standing for its value
 
-use crate::grid::neighbourhood::NType;
+mod compile;
+mod lexer;
 
-pub struct ParsedCA<'a>{
-    name: Option<&'a str>,
-    neighbourhoods: Vec<NType>,
-    states: Option<&'a str>,
+use std::str::FromStr;
+
+use crate::automaton::Automaton2D;
+use crate::errors::{CaError, ParseError};
+use crate::grid::neighbourhood::{relative_neighbourhood_coords, NType, Neighbours, MAX_DISTANCE};
+use crate::rule::DynRules;
+use crate::state::{Rgb, State, StateInfo, StateSet};
+use lexer::{Token, TokenKind};
+
+const KEYWORDS: [&str; 12] = [
+    "name", "states", "neighbourhood", "transitions", "when", "and", "or", "not", "in", "count", "sum", "cell",
+];
+
+/// Neighbourhood declared with `neighbourhood [<name>]: <type>`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NeighbourhoodDecl {
+    name: Option<String>,
+    n_type: NType,
+    size: Option<i32>,
+}
+
+impl NeighbourhoodDecl {
+    pub fn new(name: Option<String>, n_type: NType, size: Option<i32>) -> Self {
+        Self { name, n_type, size }
+    }
+
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+
+    pub fn n_type(&self) -> NType { self.n_type }
+
+    pub fn size(&self) -> Option<i32> { self.size }
+
+    /// Offsets of the cells in the neighbourhood
+    pub fn coords(&self) -> Neighbours {
+        relative_neighbourhood_coords(self.n_type, self.size).unwrap_or_default()
+    }
+}
+
+/// Rule declared with `<from> -> <to> [when <condition>]`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransitionDecl {
+    from: String,
+    to: String,
+    condition: Option<Condition>,
+}
+
+impl TransitionDecl {
+    pub fn new(from: String, to: String, condition: Option<Condition>) -> Self {
+        Self { from, to, condition }
+    }
+
+    pub fn from(&self) -> &str { &self.from }
+
+    pub fn to(&self) -> &str { &self.to }
+
+    pub fn condition(&self) -> Option<&Condition> { self.condition.as_ref() }
+}
+
+/// Condition under which a transition happens
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Compare(Expr, CompareOp, Expr),
+    /// Value is in the inclusive range
+    InRange(Expr, i64, i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A value computed from a cell's neighbourhood
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    /// Number of neighbours in any of the states, in the named or default
+    /// neighbourhood
+    Count {
+        states: Vec<String>,
+        neighbourhood: Option<String>,
+    },
+    /// Sum of the values of the neighbours in the named or default
+    /// neighbourhood
+    Sum { neighbourhood: Option<String> },
+    /// Value of the cell at an offset
+    Cell(i32, i32),
+    Number(i64),
+    /// Value of a state
+    State(String),
+}
+
+/// A parsed CA description, see the [module documentation](self) for the
+/// format
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParsedCA {
+    name: Option<String>,
+    neighbourhoods: Vec<NeighbourhoodDecl>,
+    states: StateSet,
     string: Option<String>,
-    transitions: Option<&'a str>,
+    transitions: Vec<TransitionDecl>,
 }
 
-impl<'a> ParsedCA<'a> {
-    pub fn new() -> Self { 
-        Self { 
+impl ParsedCA {
+    pub fn new() -> Self {
+        Self {
             string: None,
             name: None,
             neighbourhoods: Vec::new(),
-            states: None,
-            transitions: None,
+            states: StateSet::new(),
+            transitions: Vec::new(),
         }
     }
 
 
-    pub fn name(&self) -> Option<&str> { self.name }
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
 
-    pub fn neighbourhoods(&self) -> &[NType] { self.neighbourhoods.as_ref() }
+    pub fn neighbourhoods(&self) -> &[NeighbourhoodDecl] { self.neighbourhoods.as_ref() }
 
-    pub fn states(&self) -> Option<&str> { self.states }
+    /// States declared with `<name> = <value>`, the value is the state's id
+    pub fn states(&self) -> &StateSet { &self.states }
 
     pub fn string(&self) -> Option<&String> { self.string.as_ref() }
 
-    pub fn transitions(&self) -> Option<&str> { self.transitions }
+    pub fn transitions(&self) -> &[TransitionDecl] { self.transitions.as_ref() }
 
-    pub fn set_name(&mut self, name: Option<&'a str>) { self.name = name; }
+    pub fn set_name(&mut self, name: Option<String>) { self.name = name; }
 
-    pub fn set_neighbourhoods(&mut self, neighbourhoods: Vec<NType>) { self.neighbourhoods = neighbourhoods; }
+    pub fn set_neighbourhoods(&mut self, neighbourhoods: Vec<NeighbourhoodDecl>) { self.neighbourhoods = neighbourhoods; }
 
-    pub fn set_states(&mut self, states: Option<&'a str>) { self.states = states; }
+    pub fn set_states(&mut self, states: StateSet) { self.states = states; }
 
     pub fn set_string(&mut self, string: Option<String>) { self.string = string; }
 
-    pub fn set_transitions(&mut self, transitions: Option<&'a str>) { self.transitions = transitions; }
+    pub fn set_transitions(&mut self, transitions: Vec<TransitionDecl>) { self.transitions = transitions; }
+
+    /// Compile the transitions into [`DynRules`], whose neighbourhood is every
+    /// declared neighbourhood followed by the offsets used by `cell(x, y)`.
+    /// Fails if a state or neighbourhood isn't declared
+    pub fn compile(&self) -> Result<DynRules, CaError> {
+        compile::compile(self)
+    }
+
+    /// Return an automaton running the compiled transitions, with the
+    /// declared states
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::parse::parse_ca_string;
+    /// use ca::grid::point::Point;
+    /// use ca::state::State;
+    ///
+    /// let pca = parse_ca_string("
+    ///     name: Life
+    ///     states: dead = 0, alive = 1
+    ///     neighbourhood: moore
+    ///     transitions:
+    ///         dead -> alive when count(alive) == 3
+    ///         alive -> dead when count(alive) < 2 or count(alive) > 3
+    /// ").unwrap();
+    /// let mut automaton = pca.automaton(5, 5).unwrap();
+    /// let alive = pca.states().get("alive").unwrap();
+    /// automaton.set_seed(&[(Point::new(1, 2), alive), (Point::new(2, 2), alive), (Point::new(3, 2), alive)]).unwrap();
+    /// automaton.step();
+    /// assert_eq!(automaton.grid().get(Point::new(2, 1)), Some(&alive));
+    /// assert_eq!(automaton.grid().get(Point::new(1, 2)), Some(&State::new(0)));
+    /// ```
+    pub fn automaton(&self, width: usize, height: usize) -> Result<Automaton2D<DynRules>, CaError> {
+        let mut automaton = Automaton2D::new(width, height, self.compile()?);
+        automaton.set_states(self.states.clone());
+        Ok(automaton)
+    }
 }
 
-pub fn parse_ca_string<'a>(string: &str) -> ParsedCA<'a> {
-    let mut pca = ParsedCA::new();
+/// Parse a CA description, see the [module documentation](self) for the
+/// format
+pub fn parse_ca_string(string: &str) -> Result<ParsedCA, ParseError> {
+    let mut parser = Parser {
+        tokens: lexer::tokenize(string)?,
+        pos: 0,
+        pca: ParsedCA::new(),
+    };
+    parser.parse()?;
+    let mut pca = parser.pca;
     pca.set_string(Some(string.to_string()));
-    pca
+    Ok(pca)
+}
+
+impl FromStr for ParsedCA {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_ca_string(s)
+    }
+}
 
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    pca: ParsedCA,
 }
 
-impl<'a> From<String> for ParsedCA<'a> {
-    fn from(value: String) -> Self {
-        parse_ca_string(&value)
+impl Parser {
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.pos].kind
+    }
+
+    fn next(&mut self) -> &Token {
+        let token = &self.tokens[self.pos];
+        self.pos = (self.pos + 1).min(self.tokens.len() - 1);
+        token
+    }
+
+    /// Error for the current token not being any of the `expected` ones
+    fn error(&self, expected: &[&str]) -> ParseError {
+        let token = &self.tokens[self.pos];
+        let expected = expected.iter().map(|e| e.to_string()).collect();
+        ParseError::unexpected(token.line, token.column, self.text(self.pos), expected)
+    }
+
+    /// Error for the token at `pos` being invalid where it is
+    fn invalid(&self, pos: usize, reason: String) -> ParseError {
+        let token = &self.tokens[pos];
+        ParseError::invalid(token.line, token.column, self.text(pos), reason)
+    }
+
+    fn text(&self, pos: usize) -> String {
+        match &self.tokens[pos].kind {
+            TokenKind::Eof => String::new(),
+            kind => kind.to_string(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), TokenKind::Ident(s) if s == keyword)
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), ParseError> {
+        if *self.peek() != kind {
+            return Err(self.error(&[&format!("'{}'", kind)]));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        self.expect(TokenKind::Ident(keyword.to_string()))
+    }
+
+    /// Expect a name that isn't a keyword
+    fn ident(&mut self, what: &str) -> Result<String, ParseError> {
+        match self.peek().clone() {
+            TokenKind::Ident(s) if !KEYWORDS.contains(&s.as_str()) => {
+                self.next();
+                Ok(s)
+            }
+            _ => Err(self.error(&[what])),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, ParseError> {
+        match *self.peek() {
+            TokenKind::Number(n) => {
+                self.next();
+                Ok(n)
+            }
+            _ => Err(self.error(&["a number"])),
+        }
+    }
+
+    /// Expect a cell offset, which must fit in an `i32`
+    fn offset(&mut self) -> Result<i32, ParseError> {
+        let pos = self.pos;
+        let n = self.number()?;
+        i32::try_from(n).map_err(|_| self.invalid(pos, format!("offset {} is too large", n)))
+    }
+
+    /// Expect the name of a declared state
+    fn state(&mut self) -> Result<String, ParseError> {
+        let pos = self.pos;
+        let name = self.ident("a state")?;
+        if self.pca.states.get(&name).is_none() {
+            return Err(self.invalid(pos, format!("undeclared state '{}'", name)));
+        }
+        Ok(name)
+    }
+
+    fn parse(&mut self) -> Result<(), ParseError> {
+        while *self.peek() != TokenKind::Eof {
+            match self.peek() {
+                TokenKind::Ident(s) if s == "name" => self.name()?,
+                TokenKind::Ident(s) if s == "states" => self.states()?,
+                TokenKind::Ident(s) if s == "neighbourhood" => self.neighbourhood()?,
+                TokenKind::Ident(s) if s == "transitions" => self.transitions()?,
+                _ => return Err(self.error(&["'name'", "'states'", "'neighbourhood'", "'transitions'"])),
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<(), ParseError> {
+        self.keyword("name")?;
+        self.expect(TokenKind::Colon)?;
+        let name = match self.peek().clone() {
+            TokenKind::Str(s) | TokenKind::Ident(s) => s,
+            _ => return Err(self.error(&["a name"])),
+        };
+        self.next();
+        self.pca.name = Some(name);
+        Ok(())
+    }
+
+    fn states(&mut self) -> Result<(), ParseError> {
+        self.keyword("states")?;
+        self.expect(TokenKind::Colon)?;
+        loop {
+            let name_pos = self.pos;
+            let name = self.ident("a state name")?;
+            self.expect(TokenKind::Assign)?;
+            let value_pos = self.pos;
+            let value = self.number()?;
+            let value = u8::try_from(value)
+                .map_err(|_| self.invalid(value_pos, format!("state value {} is not between 0 and 255", value)))?;
+            let states = &self.pca.states;
+            let conflict = match states.get(&name) {
+                Some(s) => Some((name.as_str(), s.id())),
+                None => states.name(State::new(value)).map(|n| (n, value)),
+            };
+            if let Some((other, id)) = conflict {
+                let reason = format!("'{}' conflicts with state {} = {}", name, other, id);
+                return Err(self.invalid(name_pos, reason));
+            }
+            self.pca.states.insert(name, State::new(value));
+            if *self.peek() == TokenKind::LParen {
+                self.state_info(State::new(value))?;
+            }
+            if *self.peek() != TokenKind::Comma {
+                return Ok(());
+            }
+            self.next();
+        }
+    }
+
+    /// Parse `(<attribute> = "<value>", ...)` after a state declaration
+    fn state_info(&mut self, state: State) -> Result<(), ParseError> {
+        self.expect(TokenKind::LParen)?;
+        let mut info = StateInfo::new();
+        loop {
+            let attribute = match self.peek().clone() {
+                TokenKind::Ident(s) if ["colour", "glyph", "description"].contains(&s.as_str()) => s,
+                _ => return Err(self.error(&["'colour'", "'glyph'", "'description'"])),
+            };
+            self.next();
+            self.expect(TokenKind::Assign)?;
+            let value_pos = self.pos;
+            let TokenKind::Str(value) = self.peek().clone() else {
+                return Err(self.error(&["a string"]));
+            };
+            self.next();
+            match attribute.as_str() {
+                "colour" => {
+                    let colour = Rgb::from_hex(&value)
+                        .ok_or_else(|| self.invalid(value_pos, format!("invalid colour \"{}\", expected \"#rrggbb\"", value)))?;
+                    info.set_colour(Some(colour));
+                }
+                "glyph" => {
+                    let mut chars = value.chars();
+                    let (Some(glyph), None) = (chars.next(), chars.next()) else {
+                        return Err(self.invalid(value_pos, "a glyph must be a single character".into()));
+                    };
+                    info.set_glyph(Some(glyph));
+                }
+                _ => info.set_description(Some(value)),
+            }
+            if *self.peek() != TokenKind::Comma {
+                break;
+            }
+            self.next();
+        }
+        self.expect(TokenKind::RParen)?;
+        if let Some(i) = self.pca.states.info_mut(state) {
+            *i = info;
+        }
+        Ok(())
+    }
+
+    fn neighbourhood(&mut self) -> Result<(), ParseError> {
+        self.keyword("neighbourhood")?;
+        let name_pos = self.pos;
+        let name = match self.peek() {
+            TokenKind::Colon => None,
+            _ => Some(self.ident("a neighbourhood name")?),
+        };
+        if let Some(n) = self.pca.neighbourhoods.iter().find(|n| name.is_some() && n.name == name) {
+            let reason = format!("neighbourhood '{}' declared twice", n.name().unwrap_or(""));
+            return Err(self.invalid(name_pos, reason));
+        }
+        self.expect(TokenKind::Colon)?;
+        let (n_type, size) = match self.peek() {
+            TokenKind::Ident(s) if s == "moore" => (NType::Moore, None),
+            TokenKind::Ident(s) if s == "von_neumann" => (NType::VonNeumann, None),
+            TokenKind::Ident(s) if s == "hexagonal" => (NType::Hexagonal, None),
+            TokenKind::Ident(s) if s == "chebyshev" => (NType::ChebyshevDistance, Some(0)),
+            TokenKind::Ident(s) if s == "manhattan" => (NType::ManhattanDistance, Some(0)),
+            _ => return Err(self.error(&["a neighbourhood type"])),
+        };
+        self.next();
+        let size_pos = self.pos;
+        let size = match size {
+            Some(_) => match self.number()? {
+                n if (1..=MAX_DISTANCE as i64).contains(&n) => Some(n as i32),
+                n => return Err(self.invalid(size_pos, format!("radius {} is not between 1 and {}", n, MAX_DISTANCE))),
+            },
+            None => None,
+        };
+        self.pca.neighbourhoods.push(NeighbourhoodDecl::new(name, n_type, size));
+        Ok(())
+    }
+
+    fn transitions(&mut self) -> Result<(), ParseError> {
+        self.keyword("transitions")?;
+        self.expect(TokenKind::Colon)?;
+        while matches!(self.peek(), TokenKind::Ident(s) if !KEYWORDS.contains(&s.as_str())) {
+            let from = self.state()?;
+            self.expect(TokenKind::Arrow)?;
+            let to = self.state()?;
+            let condition = if self.is_keyword("when") {
+                self.next();
+                Some(self.condition()?)
+            } else {
+                None
+            };
+            self.pca.transitions.push(TransitionDecl::new(from, to, condition));
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.and_condition()?;
+        while self.is_keyword("or") {
+            self.next();
+            condition = Condition::Or(Box::new(condition), Box::new(self.and_condition()?));
+        }
+        Ok(condition)
+    }
+
+    fn and_condition(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.unary_condition()?;
+        while self.is_keyword("and") {
+            self.next();
+            condition = Condition::And(Box::new(condition), Box::new(self.unary_condition()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary_condition(&mut self) -> Result<Condition, ParseError> {
+        if self.is_keyword("not") {
+            self.next();
+            return Ok(Condition::Not(Box::new(self.unary_condition()?)));
+        }
+        if *self.peek() == TokenKind::LParen {
+            self.next();
+            let condition = self.condition()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(condition);
+        }
+
+        let lhs = self.expr()?;
+        if self.is_keyword("in") {
+            self.next();
+            let lo = self.number()?;
+            let hi = match self.peek() {
+                TokenKind::Range => {
+                    self.next();
+                    self.number()? - 1
+                }
+                TokenKind::RangeInclusive => {
+                    self.next();
+                    self.number()?
+                }
+                _ => return Err(self.error(&["'..'", "'..='"])),
+            };
+            return Ok(Condition::InRange(lhs, lo, hi));
+        }
+        let op = match self.peek() {
+            TokenKind::Eq => CompareOp::Eq,
+            TokenKind::Ne => CompareOp::Ne,
+            TokenKind::Lt => CompareOp::Lt,
+            TokenKind::Le => CompareOp::Le,
+            TokenKind::Gt => CompareOp::Gt,
+            TokenKind::Ge => CompareOp::Ge,
+            _ => return Err(self.error(&["'=='", "'!='", "'<'", "'<='", "'>'", "'>='", "'in'"])),
+        };
+        self.next();
+        Ok(Condition::Compare(lhs, op, self.expr()?))
+    }
+
+    /// Expect the name of a declared neighbourhood if there is one, otherwise
+    /// check there is a default neighbourhood
+    fn neighbourhood_ref(&mut self) -> Result<Option<String>, ParseError> {
+        let pos = self.pos;
+        let name = match self.peek() {
+            TokenKind::RParen => None,
+            _ => Some(self.ident("a neighbourhood")?),
+        };
+        let declared = match &name {
+            Some(_) => self.pca.neighbourhoods.iter().any(|n| n.name == name),
+            None => !self.pca.neighbourhoods.is_empty(),
+        };
+        if !declared {
+            let name = name.as_deref().unwrap_or("default");
+            return Err(self.invalid(pos, format!("undeclared neighbourhood '{}'", name)));
+        }
+        Ok(name)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().clone() {
+            TokenKind::Number(n) => {
+                self.next();
+                Ok(Expr::Number(n))
+            }
+            TokenKind::Ident(s) if s == "count" => {
+                self.next();
+                self.expect(TokenKind::LParen)?;
+                let mut states = vec![self.state()?];
+                while *self.peek() == TokenKind::Pipe {
+                    self.next();
+                    states.push(self.state()?);
+                }
+                let neighbourhood = if *self.peek() == TokenKind::Comma {
+                    self.next();
+                    match self.peek() {
+                        TokenKind::RParen => return Err(self.error(&["a neighbourhood"])),
+                        _ => self.neighbourhood_ref()?,
+                    }
+                } else {
+                    self.neighbourhood_ref()?
+                };
+                self.expect(TokenKind::RParen)?;
+                Ok(Expr::Count { states, neighbourhood })
+            }
+            TokenKind::Ident(s) if s == "sum" => {
+                self.next();
+                self.expect(TokenKind::LParen)?;
+                let neighbourhood = self.neighbourhood_ref()?;
+                self.expect(TokenKind::RParen)?;
+                Ok(Expr::Sum { neighbourhood })
+            }
+            TokenKind::Ident(s) if s == "cell" => {
+                self.next();
+                self.expect(TokenKind::LParen)?;
+                let x = self.offset()?;
+                self.expect(TokenKind::Comma)?;
+                let y = self.offset()?;
+                self.expect(TokenKind::RParen)?;
+                Ok(Expr::Cell(x, y))
+            }
+            TokenKind::Ident(s) if !KEYWORDS.contains(&s.as_str()) => Ok(Expr::State(self.state()?)),
+            _ => Err(self.error(&["a number", "a state", "'count'", "'sum'", "'cell'"])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BRIANS_BRAIN: &str = "
+        name: \"Brian's Brain\"
+        states: off = 0, on = 1, dying = 2
+        neighbourhood: moore
+        neighbourhood far: chebyshev 2
+        transitions:
+            off -> on when count(on) == 2
+            on -> dying
+            dying -> off when count(on | dying, far) < 12 or cell(0, -1) == on
+    ";
+
+    #[test]
+    fn parse_ca_string_test() {
+        let pca = parse_ca_string(BRIANS_BRAIN).unwrap();
+        assert_eq!(pca.name(), Some("Brian's Brain"));
+        assert_eq!(pca.states().get("dying"), Some(State::new(2)));
+        assert_eq!(pca.states().iter().map(|(_, n)| n).collect::<Vec<_>>(), vec!["off", "on", "dying"]);
+        assert_eq!(pca.neighbourhoods()[1], NeighbourhoodDecl::new(Some("far".into()), NType::ChebyshevDistance, Some(2)));
+        assert_eq!(pca.transitions().len(), 3);
+        assert_eq!(pca.transitions()[1], TransitionDecl::new("on".into(), "dying".into(), None));
+        assert_eq!(
+            pca.transitions()[0].condition(),
+            Some(&Condition::Compare(
+                Expr::Count { states: vec!["on".into()], neighbourhood: None },
+                CompareOp::Eq,
+                Expr::Number(2)
+            ))
+        );
+        assert!(matches!(pca.transitions()[2].condition(), Some(Condition::Or(_, _))));
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        let err = parse_ca_string("states: a = 0\ntransitions:\n  a -> b").unwrap_err();
+        assert_eq!((err.line(), err.column(), err.token()), (3, 8, "b"));
+        assert_eq!(err.message(), "undeclared state 'b'");
+        let err = parse_ca_string("states: a = 0, b = 0").unwrap_err();
+        assert_eq!((err.line(), err.column()), (1, 16));
+        let err = parse_ca_string("states: a = 0\ntransitions: a -> a when count(a) == 1").unwrap_err();
+        assert_eq!(err.message(), "undeclared neighbourhood 'default'");
+        let err = parse_ca_string("states: a = 0\n\nneighbourhood: moore\ntransitions: a -> a when count(a) 1").unwrap_err();
+        assert_eq!((err.line(), err.column()), (4, 35));
+        assert_eq!(err.expected().len(), 7);
+        assert_eq!(err.message(), "expected '==', '!=', '<', '<=', '>', '>=' or 'in' but found '1'");
+        let err = parse_ca_string("states: a = 0\ntransitions: a ->").unwrap_err();
+        assert_eq!(err.message(), "expected a state but found end of input");
+        let err = parse_ca_string("states: a = 0\ntransitions: a -> a when cell(4294967297, 0) == a").unwrap_err();
+        assert_eq!((err.line(), err.column(), err.token()), (2, 31, "4294967297"));
+        assert_eq!(err.message(), "offset 4294967297 is too large");
+    }
+
+    #[test]
+    fn state_info_test() {
+        let pca = parse_ca_string(
+            "states: off = 0, on = 1 (glyph = \"█\", colour = \"#ffcc00\"), dying = 2 (description = \"refractory\")",
+        )
+        .unwrap();
+        let states = pca.states();
+        let on = states.info(State::new(1)).unwrap();
+        assert_eq!((on.colour(), on.glyph(), on.description()), (Some(Rgb(255, 204, 0)), Some('█'), None));
+        assert_eq!(states.info(State::new(2)).and_then(|i| i.description()), Some("refractory"));
+        assert_eq!(states.info(State::new(0)), Some(&StateInfo::new()));
+
+        let err = parse_ca_string("states: a = 0 (colour = \"red\")").unwrap_err();
+        assert_eq!((err.column(), err.message().as_str()), (25, "invalid colour \"red\", expected \"#rrggbb\""));
+        let err = parse_ca_string("states: a = 0 (glyph = \"ab\")").unwrap_err();
+        assert_eq!(err.message(), "a glyph must be a single character");
+        let err = parse_ca_string("states: a = 0 (size = 1)").unwrap_err();
+        assert_eq!(err.message(), "expected 'colour', 'glyph' or 'description' but found 'size'");
+    }
+
+    #[test]
+    fn brians_brain_test() {
+        let pca: ParsedCA = BRIANS_BRAIN.parse().unwrap();
+        let mut automaton = pca.automaton(6, 6).unwrap();
+        let on = pca.states().get("on").unwrap();
+        let dying = pca.states().get("dying").unwrap();
+        automaton.set_seed(&[(crate::grid::point::Point::new(2, 2), on), (crate::grid::point::Point::new(3, 2), on)]).unwrap();
+        automaton.step();
+        assert_eq!(automaton.states(), pca.states());
+        let cells = automaton.grid().cells();
+        assert_eq!(cells.iter().filter(|c| **c == dying).count(), 2);
+        assert_eq!(cells.iter().filter(|c| **c == on).count(), 4);
     }
 }
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::errors::CaError;
+use crate::grid::point::Point;
+use crate::parse::{CompareOp, Condition, Expr, ParsedCA};
+use crate::rule::reduce::Reduce;
+use crate::rule::{DynRules, Rules};
+use crate::state::{State, StateSet};
+
+/// [`Condition`] with names resolved to states and neighbour indices
+enum Compiled {
+    And(Box<Compiled>, Box<Compiled>),
+    Or(Box<Compiled>, Box<Compiled>),
+    Not(Box<Compiled>),
+    Compare(Value, CompareOp, Value),
+    InRange(Value, i64, i64),
+}
+
+/// [`Expr`] with names resolved to states and neighbour indices
+enum Value {
+    Count(Range<usize>, Vec<State>),
+    Sum(Range<usize>),
+    Cell(usize),
+    Const(i64),
+}
+
+impl Compiled {
+    fn eval(&self, n: &[State]) -> bool {
+        match self {
+            Compiled::And(a, b) => a.eval(n) && b.eval(n),
+            Compiled::Or(a, b) => a.eval(n) || b.eval(n),
+            Compiled::Not(a) => !a.eval(n),
+            Compiled::Compare(a, op, b) => {
+                let (a, b) = (a.eval(n), b.eval(n));
+                match op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    CompareOp::Lt => a < b,
+                    CompareOp::Le => a <= b,
+                    CompareOp::Gt => a > b,
+                    CompareOp::Ge => a >= b,
+                }
+            }
+            Compiled::InRange(a, lo, hi) => (*lo..=*hi).contains(&a.eval(n)),
+        }
+    }
+}
+
+impl Value {
+    fn eval(&self, n: &[State]) -> i64 {
+        match self {
+            Value::Count(range, states) => n[range.clone()].count_any(states) as i64,
+            Value::Sum(range) => n[range.clone()].sum(),
+            Value::Cell(i) => n[*i].id() as i64,
+            Value::Const(c) => *c,
+        }
+    }
+}
+
+struct Compiler<'a> {
+    states: &'a StateSet,
+    neighbourhoods: HashMap<Option<&'a str>, Range<usize>>,
+    offsets: Vec<Point<i32>>,
+}
+
+impl<'a> Compiler<'a> {
+    fn state(&self, name: &str) -> Result<State, CaError> {
+        self.states
+            .get(name)
+            .ok_or_else(|| CaError::MissingState(format!("undeclared state '{}'", name)))
+    }
+
+    fn neighbourhood(&self, name: &Option<String>) -> Result<Range<usize>, CaError> {
+        self.neighbourhoods.get(&name.as_deref()).cloned().ok_or_else(|| {
+            let name = name.as_deref().unwrap_or("default");
+            CaError::InvalidNeighbourhood(format!("undeclared neighbourhood '{}'", name))
+        })
+    }
+
+    fn condition(&mut self, c: &Condition) -> Result<Compiled, CaError> {
+        Ok(match c {
+            Condition::And(a, b) => Compiled::And(Box::new(self.condition(a)?), Box::new(self.condition(b)?)),
+            Condition::Or(a, b) => Compiled::Or(Box::new(self.condition(a)?), Box::new(self.condition(b)?)),
+            Condition::Not(a) => Compiled::Not(Box::new(self.condition(a)?)),
+            Condition::Compare(a, op, b) => Compiled::Compare(self.value(a)?, *op, self.value(b)?),
+            Condition::InRange(a, lo, hi) => Compiled::InRange(self.value(a)?, *lo, *hi),
+        })
+    }
+
+    fn value(&mut self, e: &Expr) -> Result<Value, CaError> {
+        Ok(match e {
+            Expr::Count { states, neighbourhood } => Value::Count(
+                self.neighbourhood(neighbourhood)?,
+                states.iter().map(|s| self.state(s)).collect::<Result<_, _>>()?,
+            ),
+            Expr::Sum { neighbourhood } => Value::Sum(self.neighbourhood(neighbourhood)?),
+            Expr::Cell(x, y) => {
+                let offset = Point::new(*x, *y);
+                let default = 0..self.neighbourhoods.get(&None).map_or(0, |r| r.end);
+                let i = match self.offsets[default].iter().position(|o| *o == offset) {
+                    Some(i) => i,
+                    None => {
+                        self.offsets.push(offset);
+                        self.offsets.len() - 1
+                    }
+                };
+                Value::Cell(i)
+            }
+            Expr::Number(n) => Value::Const(*n),
+            Expr::State(s) => Value::Const(self.state(s)?.id() as i64),
+        })
+    }
+}
+
+pub(super) fn compile(pca: &ParsedCA) -> Result<DynRules, CaError> {
+    let mut compiler = Compiler {
+        states: &pca.states,
+        neighbourhoods: HashMap::new(),
+        offsets: Vec::new(),
+    };
+    for (i, n) in pca.neighbourhoods.iter().enumerate() {
+        let start = compiler.offsets.len();
+        compiler.offsets.extend(n.coords());
+        let range = start..compiler.offsets.len();
+        if i == 0 {
+            compiler.neighbourhoods.insert(None, range.clone());
+        }
+        if let Some(name) = n.name() {
+            compiler.neighbourhoods.insert(Some(name), range);
+        }
+    }
+
+    // transitions out of each state, in the order they were declared
+    let mut by_state: HashMap<State, Vec<(Option<Compiled>, State)>> = HashMap::new();
+    for t in pca.transitions.iter() {
+        let from = compiler.state(&t.from)?;
+        let to = compiler.state(&t.to)?;
+        let condition = t.condition.as_ref().map(|c| compiler.condition(c)).transpose()?;
+        by_state.entry(from).or_default().push((condition, to));
+    }
+
+    let mut rules: DynRules = Rules::new();
+    for (from, transitions) in by_state {
+        rules.insert(
+            from,
+            Box::new(move |n: &[State]| {
+                transitions
+                    .iter()
+                    .find(|(c, _)| c.as_ref().is_none_or(|c| c.eval(n)))
+                    .map_or(from, |(_, to)| *to)
+            }),
+        );
+    }
+    rules.set_neighbourhood(compiler.offsets);
+    rules.set_n_states(Some(pca.states.n_states()).filter(|n| *n > 0));
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::point::IsPoint;
+    use crate::parse::parse_ca_string;
+    use crate::rule::{next_ids, Rule};
+    use crate::state::StateSet;
+
+    #[test]
+    fn compile_test() {
+        let pca = parse_ca_string(
+            "states: a = 0, b = 1, c = 5
+            neighbourhood: von_neumann
+            neighbourhood big: manhattan 2
+            transitions:
+                a -> c when sum() in 2..4 and not cell(0, -1) == b
+                a -> b when count(b | c, big) >= 10
+                b -> a",
+        )
+        .unwrap();
+        let rules = pca.compile().unwrap();
+        assert_eq!(rules.neighbourhood().len(), 4 + 12);
+
+        let mut n = vec![0; 16];
+        let north = rules.neighbourhood().iter().position(|p| p.x() == 0 && p.y() == -1).unwrap();
+        assert_eq!(next_ids(&rules, 0, &n), 0);
+        assert_eq!(next_ids(&rules, 1, &n), 0);
+        assert_eq!(next_ids(&rules, 5, &n), 5);
+        n[(north + 1) % 4] = 1;
+        n[(north + 2) % 4] = 1;
+        assert_eq!(next_ids(&rules, 0, &n), 5);
+        n[north] = 1;
+        assert_eq!(next_ids(&rules, 0, &n), 0);
+        n[4..14].iter_mut().for_each(|s| *s = 5);
+        assert_eq!(next_ids(&rules, 0, &n), 1);
+    }
+
+    #[test]
+    fn undeclared_state_test() {
+        let mut pca = parse_ca_string("states: a = 0 transitions: a -> a").unwrap();
+        pca.set_states(StateSet::new());
+        let err = pca.compile().err().unwrap();
+        assert_eq!(err.to_string(), "missing state: undeclared state 'a'");
+    }
+}
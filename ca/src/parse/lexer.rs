@@ -0,0 +1,221 @@
+use std::fmt::Display;
+
+use crate::errors::ParseError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    Number(i64),
+    Str(String),
+    Arrow,
+    Colon,
+    Comma,
+    Assign,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Range,
+    RangeInclusive,
+    LParen,
+    RParen,
+    Pipe,
+    Eof,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Ident(s) => write!(f, "{}", s),
+            TokenKind::Number(n) => write!(f, "{}", n),
+            TokenKind::Str(s) => write!(f, "\"{}\"", s),
+            TokenKind::Arrow => write!(f, "->"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Assign => write!(f, "="),
+            TokenKind::Eq => write!(f, "=="),
+            TokenKind::Ne => write!(f, "!="),
+            TokenKind::Lt => write!(f, "<"),
+            TokenKind::Le => write!(f, "<="),
+            TokenKind::Gt => write!(f, ">"),
+            TokenKind::Ge => write!(f, ">="),
+            TokenKind::Range => write!(f, ".."),
+            TokenKind::RangeInclusive => write!(f, "..="),
+            TokenKind::LParen => write!(f, "("),
+            TokenKind::RParen => write!(f, ")"),
+            TokenKind::Pipe => write!(f, "|"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// A token along with the 1-indexed line and column it starts on
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Split a CA description into tokens, skipping whitespace and `#` comments.
+/// The last token is always [`TokenKind::Eof`]
+pub(crate) fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let mut take = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            let c = chars.next();
+            if c == Some('\n') {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+            c
+        };
+
+        let kind = match c {
+            '\n' | ' ' | '\t' | '\r' => {
+                take(&mut chars);
+                continue;
+            }
+            '#' => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    take(&mut chars);
+                }
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(c) = chars.peek().copied().filter(|c| c.is_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                    take(&mut chars);
+                }
+                TokenKind::Ident(ident)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                number.push(c);
+                take(&mut chars);
+                if c == '-' && chars.peek() == Some(&'>') {
+                    take(&mut chars);
+                    TokenKind::Arrow
+                } else {
+                    while let Some(c) = chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+                        number.push(c);
+                        take(&mut chars);
+                    }
+                    TokenKind::Number(number.parse().map_err(|_| {
+                        ParseError::invalid(start_line, start_column, &number, format!("invalid number '{}'", number))
+                    })?)
+                }
+            }
+            '"' => {
+                take(&mut chars);
+                let mut string = String::new();
+                loop {
+                    match take(&mut chars) {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(ParseError::invalid(start_line, start_column, "\"", "unterminated string"))
+                        }
+                        Some(c) => string.push(c),
+                    }
+                }
+                TokenKind::Str(string)
+            }
+            _ => {
+                take(&mut chars);
+                let next = chars.peek().copied();
+                let mut two = |kind| {
+                    take(&mut chars);
+                    kind
+                };
+                match (c, next) {
+                    ('=', Some('=')) => two(TokenKind::Eq),
+                    ('!', Some('=')) => two(TokenKind::Ne),
+                    ('<', Some('=')) => two(TokenKind::Le),
+                    ('>', Some('=')) => two(TokenKind::Ge),
+                    ('.', Some('.')) => {
+                        take(&mut chars);
+                        if chars.peek() == Some(&'=') {
+                            take(&mut chars);
+                            TokenKind::RangeInclusive
+                        } else {
+                            TokenKind::Range
+                        }
+                    }
+                    ('=', _) => TokenKind::Assign,
+                    ('<', _) => TokenKind::Lt,
+                    ('>', _) => TokenKind::Gt,
+                    (':', _) => TokenKind::Colon,
+                    (',', _) => TokenKind::Comma,
+                    ('(', _) => TokenKind::LParen,
+                    (')', _) => TokenKind::RParen,
+                    ('|', _) => TokenKind::Pipe,
+                    _ => {
+                        let reason = format!("unexpected character '{}'", c);
+                        return Err(ParseError::invalid(start_line, start_column, c, reason));
+                    }
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_test() {
+        let tokens = tokenize("on -> off # comment\n  when cell(-1, 0) in 2..=3").unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident("on".into()),
+                TokenKind::Arrow,
+                TokenKind::Ident("off".into()),
+                TokenKind::Ident("when".into()),
+                TokenKind::Ident("cell".into()),
+                TokenKind::LParen,
+                TokenKind::Number(-1),
+                TokenKind::Comma,
+                TokenKind::Number(0),
+                TokenKind::RParen,
+                TokenKind::Ident("in".into()),
+                TokenKind::Number(2),
+                TokenKind::RangeInclusive,
+                TokenKind::Number(3),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 3));
+    }
+
+    #[test]
+    fn tokenize_error_test() {
+        let err = tokenize("a\n  b ? c").unwrap_err();
+        assert_eq!((err.line(), err.column(), err.token()), (2, 5, "?"));
+        let err = tokenize("name: \"open").unwrap_err();
+        assert_eq!((err.line(), err.column()), (1, 7));
+    }
+}
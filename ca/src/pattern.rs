@@ -0,0 +1,177 @@
+//! Reading and writing pattern files. Every format is read into a
+//! [`Pattern`], whose cells can be passed to
+//! [`Automaton2D::set_seed`](crate::automaton::Automaton2D::set_seed)
+
+pub mod apgcode;
+pub mod cells;
+pub mod life;
+pub mod macrocell;
+pub mod rle;
+
+use std::path::Path;
+
+use crate::errors::CaError;
+use crate::grid::dense_grid::DenseGrid;
+use crate::grid::point::{IsPoint, Point};
+use crate::state::State;
+
+/// Cells of a pattern along with the metadata of the file it came from
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::dense_grid::DenseGrid;
+/// use ca::grid::point::Point;
+/// use ca::pattern::Pattern;
+/// use ca::state::State;
+///
+/// let mut grid = DenseGrid::new(5, 5);
+/// grid.set(Point::new(2, 3), State::new(1));
+/// let pattern = Pattern::from_grid(&grid, Point::new(1, 1), 3, 3);
+/// assert_eq!(pattern.cells(), &[(Point::new(1, 2), State::new(1))]);
+/// assert_eq!(pattern.cells_at(Point::new(1, 1))[0].0, Point::new(2, 3));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pattern {
+    width: usize,
+    height: usize,
+    rule: Option<String>,
+    name: Option<String>,
+    author: Option<String>,
+    comments: Vec<String>,
+    cells: Vec<(Point<i32>, State)>,
+}
+
+impl Pattern {
+    /// Return a new `width` by `height` pattern of the given cells, relative
+    /// to its top left corner. Cells in state 0 are dropped
+    pub fn new(width: usize, height: usize, cells: Vec<(Point<i32>, State)>) -> Pattern {
+        Pattern {
+            width,
+            height,
+            cells: cells.into_iter().filter(|(_, s)| s.id() != 0).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Pattern just large enough for the cells, moved so the top left corner
+    /// of their bounding box is the origin
+    pub fn bounded(cells: Vec<(Point<i32>, State)>) -> Pattern {
+        let cells: Vec<_> = cells.into_iter().filter(|(_, s)| s.id() != 0).collect();
+        let (Some(min_x), Some(min_y)) = (cells.iter().map(|(p, _)| p.x()).min(), cells.iter().map(|(p, _)| p.y()).min())
+        else {
+            return Pattern::default();
+        };
+        let max_x = cells.iter().map(|(p, _)| p.x()).max().unwrap();
+        let max_y = cells.iter().map(|(p, _)| p.y()).max().unwrap();
+        let mut cells: Vec<_> = cells.into_iter().map(|(p, s)| (p + Point::new(-min_x, -min_y), s)).collect();
+        cells.sort_by_key(|(p, _)| (p.y(), p.x()));
+        cells.dedup_by_key(|(p, _)| *p);
+        Pattern::new((max_x - min_x) as usize + 1, (max_y - min_y) as usize + 1, cells)
+    }
+
+    /// Pattern of the `width` by `height` region of `grid` whose top left
+    /// corner is `origin`
+    pub fn from_grid(grid: &DenseGrid<State>, origin: Point<i32>, width: usize, height: usize) -> Pattern {
+        let mut cells = Vec::new();
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                if let Some(s) = grid.get(origin + Point::new(x, y)) {
+                    cells.push((Point::new(x, y), *s));
+                }
+            }
+        }
+        Pattern::new(width, height, cells)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Cells not in state 0, relative to the top left corner, in row order
+    pub fn cells(&self) -> &[(Point<i32>, State)] {
+        &self.cells
+    }
+
+    /// Cells moved so the top left corner is at `origin`
+    pub fn cells_at(&self, origin: Point<i32>) -> Vec<(Point<i32>, State)> {
+        self.cells.iter().map(|(p, s)| (origin + *p, *s)).collect()
+    }
+
+    /// State of the cell at `p`, 0 if it isn't one of the cells
+    pub fn get(&self, p: Point<i32>) -> State {
+        self.cells.iter().find(|(c, _)| *c == p).map_or(State::default(), |(_, s)| *s)
+    }
+
+    /// Rule the pattern is meant to be run with
+    pub fn rule(&self) -> Option<&str> {
+        self.rule.as_deref()
+    }
+
+    pub fn set_rule(&mut self, rule: Option<String>) {
+        self.rule = rule;
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    /// Free text comment lines
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    pub fn set_comments(&mut self, comments: Vec<String>) {
+        self.comments = comments;
+    }
+
+    /// Highest state of any cell, 1 for an empty pattern
+    pub(crate) fn max_state(&self) -> State {
+        self.cells.iter().map(|(_, s)| *s).max().unwrap_or(State::new(1)).max(State::new(1))
+    }
+
+    /// Cells in a row-major `width * height` vector, cells outside of the
+    /// bounds are dropped
+    pub(crate) fn to_rows(&self) -> Vec<State> {
+        let mut rows = vec![State::default(); self.width * self.height];
+        for (p, s) in self.cells.iter() {
+            if (0..self.width as i32).contains(&p.x()) && (0..self.height as i32).contains(&p.y()) {
+                rows[p.y() as usize * self.width + p.x() as usize] = *s;
+            }
+        }
+        rows
+    }
+}
+
+/// Read a pattern file in the format given by its extension: `.rle`,
+/// `.cells`, `.mc` or `.lif`/`.life` for both Life 1.05 and 1.06
+pub fn load(path: impl AsRef<Path>) -> Result<Pattern, CaError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    let pattern = match extension.as_str() {
+        "rle" => rle::read(&text)?,
+        "cells" => cells::read(&text)?,
+        "mc" => macrocell::read(&text)?,
+        "lif" | "life" if text.starts_with("#Life 1.06") => life::read_106(&text)?,
+        "lif" | "life" => life::read_105(&text)?,
+        _ => return Err(CaError::InvalidPattern(format!("{} has an unknown extension", path.display()))),
+    };
+    Ok(pattern)
+}
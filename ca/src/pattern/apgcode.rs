@@ -0,0 +1,266 @@
+//! apgcodes, the pattern identifiers used by Catagolue such as `xs4_33` for
+//! the block, `xp2_7` for the blinker and `xq4_153` for the glider
+//!
+//! The prefix is `xs` and the population for still lifes, `xp` and the
+//! period for oscillators and `xq` and the period for spaceships. It is
+//! followed by the cells in extended Wechsler format: the pattern is cut
+//! into strips 5 rows high separated by `z`, and every column of a strip is
+//! a digit from `0` to `v` whose bits are its cells, top row first. Runs of
+//! empty columns are written `w` for 2, `x` for 3 and `y0` to `yz` for 4 to
+//! 39, and empty columns ending a strip are left out.
+//!
+//! apgcodes only have two states, cells in any state but 0 are alive.
+
+use crate::automaton::Automaton2D;
+use crate::errors::CaError;
+use crate::grid::point::{IsPoint, Point};
+use crate::pattern::Pattern;
+use crate::rule::Rule;
+use crate::state::State;
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Extended Wechsler encoding of the cells as they are, relative to their
+/// bounding box
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::pattern::apgcode;
+/// use ca::state::State;
+///
+/// let alive = State::new(1);
+/// let glider = [(0, 0), (1, 0), (2, 0), (2, 1), (1, 2)].map(|(x, y)| (Point::new(x, y), alive));
+/// assert_eq!(apgcode::wechsler(&glider), "153");
+/// ```
+pub fn wechsler(cells: &[(Point<i32>, State)]) -> String {
+    let pattern = Pattern::bounded(cells.to_vec());
+    let mut columns = vec![vec![0u8; pattern.width()]; pattern.height().div_ceil(5)];
+    for (p, _) in pattern.cells() {
+        columns[p.y() as usize / 5][p.x() as usize] |= 1 << (p.y() % 5);
+    }
+
+    let mut strips = Vec::new();
+    for strip in columns {
+        let mut code = String::new();
+        let mut zeros = 0;
+        for column in strip {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros > 39 {
+                code.push_str("yz");
+                zeros -= 39;
+            }
+            match zeros {
+                0 => {}
+                1 => code.push('0'),
+                2 => code.push('w'),
+                3 => code.push('x'),
+                n => {
+                    code.push('y');
+                    code.push(DIGITS[n - 4] as char);
+                }
+            }
+            zeros = 0;
+            code.push(DIGITS[column as usize] as char);
+        }
+        strips.push(code);
+    }
+    strips.join("z")
+}
+
+/// The 8 rotations and reflections of some cells
+fn orientations(cells: &[(Point<i32>, State)]) -> impl Iterator<Item = Vec<(Point<i32>, State)>> + '_ {
+    // (x, y) becomes (a * x + b * y, c * x + d * y)
+    let transforms = [
+        (1, 0, 0, 1),
+        (-1, 0, 0, 1),
+        (1, 0, 0, -1),
+        (-1, 0, 0, -1),
+        (0, 1, 1, 0),
+        (0, -1, 1, 0),
+        (0, 1, -1, 0),
+        (0, -1, -1, 0),
+    ];
+    transforms.into_iter().map(move |(a, b, c, d)| {
+        cells
+            .iter()
+            .map(|(p, s)| (Point::new(a * p.x() + b * p.y(), c * p.x() + d * p.y()), *s))
+            .collect()
+    })
+}
+
+/// Shortest, then alphabetically first, Wechsler encoding of any orientation
+/// of any of the phases of an object
+pub fn canonical(phases: &[Vec<(Point<i32>, State)>]) -> String {
+    phases
+        .iter()
+        .flat_map(|cells| orientations(cells))
+        .map(|cells| wechsler(&cells))
+        .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+        .unwrap_or_default()
+}
+
+/// Run `pattern` with `rule` for up to `max_period` generations and return
+/// its apgcode if it returns to its starting shape, `None` if it doesn't
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::{apgcode, rle};
+/// use ca::rule::builder::{state, RuleBuilder};
+/// use ca::state::StateSet;
+///
+/// let mut states = StateSet::new();
+/// states.push("dead");
+/// states.push("alive");
+/// let life = RuleBuilder::new()
+///     .when(state("dead")).count(state("alive")).equals(3).then(state("alive"))
+///     .when(state("alive")).count(state("alive")).in_range(2..=3).then(state("alive"))
+///     .when(state("alive")).then(state("dead"))
+///     .build(&states)
+///     .unwrap();
+///
+/// let glider = rle::read("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+/// assert_eq!(apgcode::identify(&glider, life, 8).as_deref(), Some("xq4_153"));
+/// ```
+pub fn identify<R: Rule>(pattern: &Pattern, rule: R, max_period: usize) -> Option<String> {
+    let start = Pattern::bounded(alive(pattern.cells()));
+    if start.cells().is_empty() {
+        return None;
+    }
+    // leave room for a spaceship moving one cell per generation
+    let margin = max_period as i32 + 2;
+    let width = start.width() + 2 * margin as usize;
+    let height = start.height() + 2 * margin as usize;
+    let mut automaton = Automaton2D::new(width, height, rule);
+    automaton.set_seed(&start.cells_at(Point::new(margin, margin))).ok()?;
+
+    let mut phases = vec![start.cells().to_vec()];
+    for period in 1..=max_period {
+        automaton.step();
+        let cells: Vec<_> = automaton.grid().iter().filter(|(_, s)| s.id() != 0).map(|(p, s)| (p, *s)).collect();
+        let min = |f: fn(&Point<i32>) -> i32| cells.iter().map(|(p, _)| f(p)).min();
+        let (Some(x), Some(y)) = (min(|p| p.x()), min(|p| p.y())) else {
+            return None;
+        };
+        let phase = Pattern::bounded(alive(&cells));
+        if phase.cells() == start.cells() {
+            let code = canonical(&phases);
+            return Some(match (period, (x, y) == (margin, margin)) {
+                (1, true) => format!("xs{}_{}", start.cells().len(), code),
+                (_, true) => format!("xp{}_{}", period, code),
+                (_, false) => format!("xq{}_{}", period, code),
+            });
+        }
+        phases.push(phase.cells().to_vec());
+    }
+    None
+}
+
+/// Cells in any state but 0 as state 1
+fn alive(cells: &[(Point<i32>, State)]) -> Vec<(Point<i32>, State)> {
+    cells.iter().filter(|(_, s)| s.id() != 0).map(|(p, _)| (*p, State::new(1))).collect()
+}
+
+/// Cells of an apgcode, or of a Wechsler encoding without a prefix
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::apgcode;
+///
+/// let block = apgcode::decode("xs4_33").unwrap();
+/// assert_eq!((block.width(), block.height()), (2, 2));
+/// assert_eq!(block.cells().len(), 4);
+/// ```
+pub fn decode(code: &str) -> Result<Pattern, CaError> {
+    let err = |reason: String| CaError::InvalidPattern(format!("{:?} {}", code, reason));
+    let body = match code.split_once('_') {
+        Some((prefix, body)) => {
+            let valid = ["xs", "xp", "xq"]
+                .iter()
+                .any(|p| prefix.strip_prefix(p).is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())));
+            if !valid {
+                return Err(err(format!("has an invalid prefix {:?}", prefix)));
+            }
+            body
+        }
+        None => code,
+    };
+
+    let mut cells = Vec::new();
+    let (mut x, mut strip) = (0, 0);
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            'w' => x += 2,
+            'x' => x += 3,
+            'y' => {
+                let n = chars.next().and_then(|n| n.to_digit(36)).ok_or_else(|| err("has an invalid run".to_string()))?;
+                x += 4 + n as i32;
+            }
+            'z' => (x, strip) = (0, strip + 1),
+            c => {
+                let column = c.to_digit(32).ok_or_else(|| err(format!("has an invalid column {:?}", c)))?;
+                for bit in 0..5 {
+                    if column & (1 << bit) != 0 {
+                        cells.push((Point::new(x, strip * 5 + bit), State::new(1)));
+                    }
+                }
+                x += 1;
+            }
+        }
+    }
+    Ok(Pattern::bounded(cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::rle;
+    use crate::rule::builder::{state, RuleBuilder};
+    use crate::rule::builder::RuleSet;
+    use crate::state::StateSet;
+
+    fn life() -> RuleSet {
+        let mut states = StateSet::new();
+        states.push("dead");
+        states.push("alive");
+        RuleBuilder::new()
+            .when(state("dead")).count(state("alive")).equals(3).then(state("alive"))
+            .when(state("alive")).count(state("alive")).in_range(2..=3).then(state("alive"))
+            .when(state("alive")).then(state("dead"))
+            .build(&states)
+            .unwrap()
+    }
+
+    #[test]
+    fn identify_test() {
+        let id = |rle: &str| identify(&rle::read(rle).unwrap(), life(), 20);
+        assert_eq!(id("x = 2, y = 2\n2o$2o!").as_deref(), Some("xs4_33"));
+        assert_eq!(id("x = 3, y = 1\n3o!").as_deref(), Some("xp2_7"));
+        assert_eq!(id("x = 4, y = 3\nb2o$o2bo$b2o!").as_deref(), Some("xs6_696"));
+        // lightweight spaceship
+        assert_eq!(id("x = 5, y = 4\nbo2bo$o4b$o3bo$4o!").as_deref(), Some("xq4_6frc"));
+        assert_eq!(id("x = 1, y = 1\no!"), None);
+    }
+
+    #[test]
+    fn wechsler_test() {
+        // two cells far apart in different strips
+        let cells = [(Point::new(0, 0), State::new(1)), (Point::new(45, 11), State::new(1))];
+        let code = wechsler(&cells);
+        assert_eq!(code, "1zzyzy22");
+        let expected: Vec<_> = cells.to_vec();
+        assert_eq!(decode(&code).unwrap().cells(), &expected[..]);
+
+        let glider = decode("xq4_153").unwrap();
+        assert_eq!(rle::write(&glider), "x = 3, y = 3\n3o$2bo$bo!\n");
+        assert!(matches!(decode("xs4_3!"), Err(CaError::InvalidPattern(_))));
+        assert!(decode("xr4_33").is_err());
+    }
+}
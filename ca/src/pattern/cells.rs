@@ -0,0 +1,89 @@
+//! Plaintext `.cells` files, as used by the LifeWiki
+//!
+//! Lines starting with `!` are comments, `!Name:` and `!Author:` give the
+//! name and author. Every other line is a row of `.` for dead and `O` for
+//! live cells, trailing dead cells may be left out.
+
+use crate::errors::LoadError;
+use crate::grid::point::Point;
+use crate::pattern::Pattern;
+use crate::state::State;
+
+/// Read a `.cells` file, `*` is also accepted for live cells
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::cells;
+///
+/// let glider = cells::read("!Name: Glider\n.O\n..O\nOOO\n").unwrap();
+/// assert_eq!(glider.name(), Some("Glider"));
+/// assert_eq!((glider.width(), glider.height()), (3, 3));
+/// assert_eq!(glider.cells().len(), 5);
+/// ```
+pub fn read(cells: &str) -> Result<Pattern, LoadError> {
+    let mut pattern = Pattern::default();
+    let mut y = 0;
+    for (i, line) in cells.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            if let Some(name) = comment.strip_prefix("Name:") {
+                pattern.name = Some(name.trim().to_string());
+            } else if let Some(author) = comment.strip_prefix("Author:") {
+                pattern.author = Some(author.trim().to_string());
+            } else {
+                pattern.comments.push(comment.trim().to_string());
+            }
+            continue;
+        }
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | '*' => pattern.cells.push((Point::new(x as i32, y), State::new(1))),
+                c => return Err(LoadError::new(i + 1, format!("unexpected {:?}", c))),
+            }
+        }
+        pattern.width = pattern.width.max(line.chars().count());
+        y += 1;
+    }
+    pattern.height = y as usize;
+    Ok(pattern)
+}
+
+/// Write a pattern as a `.cells` file. The format only has two states, cells
+/// in any state but 0 are written as live
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if let Some(name) = pattern.name() {
+        out.push_str(&format!("!Name: {}\n", name));
+    }
+    if let Some(author) = pattern.author() {
+        out.push_str(&format!("!Author: {}\n", author));
+    }
+    for comment in pattern.comments() {
+        out.push_str(&format!("!{}\n", comment));
+    }
+    for row in pattern.to_rows().chunks(pattern.width().max(1)) {
+        let live = row.iter().rposition(|s| s.id() != 0).map_or(0, |x| x + 1);
+        out.extend(row[..live].iter().map(|s| if s.id() == 0 { '.' } else { 'O' }));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_round_trip_test() {
+        let file = "!Name: Beehive\n!Author: John Conway\n!A still life\n\n.OO\nO..O\n.OO\n";
+        let pattern = read(file).unwrap();
+        assert_eq!(pattern.author(), Some("John Conway"));
+        assert_eq!(pattern.comments(), &["A still life".to_string()]);
+        assert_eq!((pattern.width(), pattern.height()), (4, 4));
+        assert_eq!(pattern.get(Point::new(3, 2)), State::new(1));
+        assert_eq!(write(&pattern), file);
+        assert_eq!(read("..\n.x").unwrap_err().line(), 2);
+    }
+}
@@ -0,0 +1,185 @@
+//! Life 1.05 and Life 1.06 pattern files
+//!
+//! Both start with a `#Life 1.05` or `#Life 1.06` line. Life 1.05 has `#D`
+//! description lines, `#N` for Conway's rule or `#R 23/3` for another
+//! survival/birth rule, and `#P x y` blocks of `.` and `*` rows placed
+//! relative to the centre. Life 1.06 is a list of `x y` live cells.
+//!
+//! Both formats only have two states, cells in any state but 0 are written
+//! as live. Patterns are read relative to the bounding box of their live
+//! cells.
+
+use crate::errors::LoadError;
+use crate::grid::point::{IsPoint, Point};
+use crate::pattern::Pattern;
+use crate::state::State;
+
+/// Read a Life 1.05 file
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::life;
+///
+/// let glider = life::read_105("#Life 1.05\n#D Glider\n#N\n#P -1 -1\n.*\n..*\n***\n").unwrap();
+/// assert_eq!(glider.rule(), Some("B3/S23"));
+/// assert_eq!(glider.comments(), &["Glider".to_string()]);
+/// assert_eq!((glider.width(), glider.height()), (3, 3));
+/// ```
+pub fn read_105(life: &str) -> Result<Pattern, LoadError> {
+    let mut cells = Vec::new();
+    let (mut comments, mut rule) = (Vec::new(), None);
+    let mut block: Option<(Point<i32>, i32)> = None;
+    for (i, line) in life.lines().enumerate() {
+        let err = |reason: String| LoadError::new(i + 1, reason);
+        let line = line.trim();
+        if i == 0 {
+            if !line.starts_with("#Life 1.05") {
+                return Err(err("missing #Life 1.05 header".to_string()));
+            }
+        } else if let Some(text) = line.strip_prefix("#D").or_else(|| line.strip_prefix("#C")) {
+            comments.push(text.trim().to_string());
+        } else if line == "#N" {
+            rule = Some("B3/S23".to_string());
+        } else if let Some(text) = line.strip_prefix("#R") {
+            let (survival, birth) = text.trim().split_once('/').ok_or_else(|| err(format!("invalid rule {:?}", text.trim())))?;
+            rule = Some(format!("B{}/S{}", birth, survival));
+        } else if let Some(text) = line.strip_prefix("#P") {
+            let mut coords = text.split_whitespace().map(|n| n.parse::<i32>());
+            match (coords.next(), coords.next()) {
+                (Some(Ok(x)), Some(Ok(y))) => block = Some((Point::new(x, y), 0)),
+                _ => return Err(err(format!("invalid block position {:?}", text.trim()))),
+            }
+        } else if line.starts_with('#') || line.is_empty() {
+            continue;
+        } else {
+            let (origin, row) = block.as_mut().ok_or_else(|| err("cells before a #P line".to_string()))?;
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' | 'O' => cells.push((*origin + Point::new(x as i32, *row), State::new(1))),
+                    c => return Err(err(format!("unexpected {:?}", c))),
+                }
+            }
+            *row += 1;
+        }
+    }
+    if life.trim().is_empty() {
+        return Err(LoadError::new(1, "missing #Life 1.05 header"));
+    }
+    let mut pattern = Pattern::bounded(cells);
+    pattern.comments = comments;
+    pattern.rule = rule;
+    Ok(pattern)
+}
+
+/// Write a pattern as Life 1.05, as a single block centred on the origin.
+/// The name is written as the first `#D` line, B/S rules as `#R` and other
+/// rules are dropped
+pub fn write_105(pattern: &Pattern) -> String {
+    let mut out = "#Life 1.05\n".to_string();
+    for comment in pattern.name().into_iter().chain(pattern.comments().iter().map(String::as_str)) {
+        out.push_str(&format!("#D {}\n", comment));
+    }
+    match pattern.rule().and_then(survival_birth) {
+        Some(rule) if rule == "23/3" => out.push_str("#N\n"),
+        Some(rule) => out.push_str(&format!("#R {}\n", rule)),
+        None => {}
+    }
+    let (width, height) = (pattern.width() as i32, pattern.height() as i32);
+    out.push_str(&format!("#P {} {}\n", -(width / 2), -(height / 2)));
+    for row in pattern.to_rows().chunks(pattern.width().max(1)) {
+        let live = row.iter().rposition(|s| s.id() != 0).map_or(0, |x| x + 1);
+        if live == 0 {
+            out.push('.');
+        }
+        out.extend(row[..live].iter().map(|s| if s.id() == 0 { '.' } else { '*' }));
+        out.push('\n');
+    }
+    out
+}
+
+/// `B3/S23` as Life 1.05's `23/3`
+fn survival_birth(rule: &str) -> Option<String> {
+    let (birth, survival) = rule.split_once('/')?;
+    let birth = birth.strip_prefix('B').or_else(|| birth.strip_prefix('b'))?;
+    let survival = survival.strip_prefix('S').or_else(|| survival.strip_prefix('s'))?;
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    (digits(birth) && digits(survival)).then(|| format!("{}/{}", survival, birth))
+}
+
+/// Read a Life 1.06 file
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::pattern::life;
+/// use ca::state::State;
+///
+/// let blinker = life::read_106("#Life 1.06\n-1 0\n0 0\n1 0\n").unwrap();
+/// assert_eq!((blinker.width(), blinker.height()), (3, 1));
+/// assert_eq!(blinker.get(Point::new(0, 0)), State::new(1));
+/// ```
+pub fn read_106(life: &str) -> Result<Pattern, LoadError> {
+    let mut cells = Vec::new();
+    for (i, line) in life.lines().enumerate() {
+        let err = |reason: String| LoadError::new(i + 1, reason);
+        let line = line.trim();
+        if i == 0 {
+            if !line.starts_with("#Life 1.06") {
+                return Err(err("missing #Life 1.06 header".to_string()));
+            }
+            continue;
+        }
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        let mut coords = line.split_whitespace().map(|n| n.parse::<i32>());
+        match (coords.next(), coords.next(), coords.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => cells.push((Point::new(x, y), State::new(1))),
+            _ => return Err(err(format!("invalid cell {:?}", line))),
+        }
+    }
+    if life.trim().is_empty() {
+        return Err(LoadError::new(1, "missing #Life 1.06 header"));
+    }
+    Ok(Pattern::bounded(cells))
+}
+
+/// Write a pattern as Life 1.06, with cells relative to its top left corner
+pub fn write_106(pattern: &Pattern) -> String {
+    let mut out = "#Life 1.06\n".to_string();
+    for (p, _) in pattern.cells() {
+        out.push_str(&format!("{} {}\n", p.x(), p.y()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn life_105_test() {
+        let file = "#Life 1.05\n#D Two blocks\n#R 23/36\n#P -3 -1\n**\n**\n#P 2 0\n**\n**\n";
+        let pattern = read_105(file).unwrap();
+        assert_eq!(pattern.rule(), Some("B36/S23"));
+        assert_eq!((pattern.width(), pattern.height()), (7, 3));
+        assert_eq!(pattern.get(Point::new(5, 1)), State::new(1));
+        let written = write_105(&pattern);
+        assert_eq!(written, "#Life 1.05\n#D Two blocks\n#R 23/36\n#P -3 -1\n**\n**...**\n.....**\n");
+        assert_eq!(read_105(&written).unwrap(), pattern);
+        assert_eq!(read_105("#Life 1.05\n.*\n").unwrap_err().line(), 2);
+    }
+
+    #[test]
+    fn life_106_test() {
+        let pattern = read_106("#Life 1.06\n# a comment\n5 -2\n6 -1\n4 0\n5 0\n6 0\n").unwrap();
+        assert_eq!((pattern.width(), pattern.height()), (3, 3));
+        assert_eq!(write_106(&pattern), "#Life 1.06\n1 0\n2 1\n0 2\n1 2\n2 2\n");
+        assert_eq!(read_106(&write_106(&pattern)).unwrap(), pattern);
+        assert!(read_106("#Life 1.05\n").is_err());
+        assert_eq!(read_106("#Life 1.06\n1 x\n").unwrap_err().line(), 2);
+    }
+}
@@ -0,0 +1,344 @@
+//! Golly's Macrocell format, a [`QuadTree`] written one node per line so
+//! shared nodes are only written once
+//!
+//! The file starts with `[M2]`, followed by `#R` rule, `#N` name and `#C`
+//! comment lines. Every node is then numbered from 1 in the order it is
+//! written, and refers to its north west, north east, south west and south
+//! east children by number, 0 being an empty child. Two state patterns write
+//! 8x8 nodes as rows of `.` and `*` ended by `$`, and larger nodes as
+//! `level nw ne sw se` with a node of `2^level` cells. Multi-state patterns
+//! write 2x2 nodes as `1 nw ne sw se` with the states of the 4 cells.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::LoadError;
+use crate::grid::quadtree::{Node, QuadTree};
+use crate::pattern::Pattern;
+use crate::state::State;
+
+/// Read a Macrocell file into a quadtree, with the metadata in a pattern
+/// without cells. The root is the last node in the file
+fn parse(mc: &str) -> Result<(QuadTree, Pattern), LoadError> {
+    let mut tree = QuadTree::new(0);
+    let mut meta = Pattern::default();
+    // tree node and level of every node in the file
+    let mut nodes: Vec<(usize, u32)> = Vec::new();
+
+    for (i, line) in mc.lines().enumerate() {
+        let err = |reason: String| LoadError::new(i + 1, reason);
+        let line = line.trim();
+        if i == 0 {
+            if !line.starts_with("[M2]") {
+                return Err(err("missing [M2] header".to_string()));
+            }
+        } else if let Some(rule) = line.strip_prefix("#R") {
+            meta.rule = Some(rule.trim().to_string());
+        } else if let Some(name) = line.strip_prefix("#N") {
+            meta.name = Some(name.trim().to_string());
+        } else if let Some(comment) = line.strip_prefix("#C") {
+            meta.comments.push(comment.trim().to_string());
+        } else if line.starts_with('#') || line.is_empty() {
+            continue;
+        } else if line.starts_with(['.', '*', '$']) {
+            let mut rows = [[false; 8]; 8];
+            let (mut x, mut y) = (0, 0);
+            for c in line.chars() {
+                match c {
+                    '.' => x += 1,
+                    '*' if x < 8 && y < 8 => {
+                        rows[y][x] = true;
+                        x += 1;
+                    }
+                    '$' => (x, y) = (0, y + 1),
+                    c => return Err(err(format!("unexpected {:?} in 8x8 node", c))),
+                }
+            }
+            nodes.push((tree.build(3, &|x, y| State::new(rows[y][x] as u8)), 3));
+        } else {
+            let numbers: Vec<usize> = line
+                .split_whitespace()
+                .map(|n| n.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| err(format!("invalid node {:?}", line)))?;
+            let [level, nw, ne, sw, se] = numbers[..] else {
+                return Err(err(format!("invalid node {:?}", line)));
+            };
+            if level == 0 || level > 62 {
+                return Err(err(format!("invalid node {:?}", line)));
+            }
+            let level = level as u32;
+            let mut children = [0; 4];
+            for (child, n) in children.iter_mut().zip([nw, ne, sw, se]) {
+                *child = if level == 1 {
+                    let state = u8::try_from(n).map_err(|_| err(format!("invalid state {}", n)))?;
+                    tree.intern(Node::Leaf(State::new(state)))
+                } else if n == 0 {
+                    tree.empty(level - 1)
+                } else {
+                    match nodes.get(n - 1) {
+                        Some((id, l)) if *l == level - 1 => *id,
+                        Some(_) => return Err(err(format!("node {} isn't of level {}", n, level - 1))),
+                        None => return Err(err(format!("node {} isn't defined yet", n))),
+                    }
+                };
+            }
+            nodes.push((tree.intern(Node::Branch { level, children }), level));
+        }
+    }
+
+    if mc.trim().is_empty() {
+        return Err(LoadError::new(1, "missing [M2] header"));
+    }
+    if let Some((root, _)) = nodes.last() {
+        tree.set_root(*root);
+    }
+    Ok((tree, meta))
+}
+
+/// Read a Macrocell file, relative to the bounding box of its cells
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::macrocell;
+///
+/// let glider = macrocell::read("[M2] (golly 4.0)\n#R B3/S23\n$$..*$...*$.***$\n").unwrap();
+/// assert_eq!(glider.rule(), Some("B3/S23"));
+/// assert_eq!((glider.width(), glider.height()), (3, 3));
+/// assert_eq!(glider.cells().len(), 5);
+/// ```
+pub fn read(mc: &str) -> Result<Pattern, LoadError> {
+    let (tree, meta) = parse(mc)?;
+    let cells = tree.bounded_cells().map_err(|e| LoadError::new(mc.lines().count(), e.to_string()))?;
+    let mut pattern = Pattern::bounded(cells);
+    pattern.rule = meta.rule;
+    pattern.name = meta.name;
+    pattern.comments = meta.comments;
+    Ok(pattern)
+}
+
+/// Read a Macrocell file into a quadtree, with the root's north west corner
+/// at the origin
+pub fn read_tree(mc: &str) -> Result<QuadTree, LoadError> {
+    parse(mc).map(|(tree, _)| tree)
+}
+
+/// Write a pattern as Macrocell
+pub fn write(pattern: &Pattern) -> String {
+    write_with(&QuadTree::from_cells(pattern.cells()), pattern)
+}
+
+/// Write a quadtree as Macrocell, using the two state format if no cell is
+/// in a state above 1
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::grid::quadtree::QuadTree;
+/// use ca::pattern::macrocell;
+/// use ca::state::State;
+///
+/// let mut tree = QuadTree::new(20);
+/// tree.set(Point::new(0, 0), State::new(1)).unwrap();
+/// tree.set(Point::new(1 << 19, 1 << 19), State::new(1)).unwrap();
+/// let mc = macrocell::write_tree(&tree, Some("B3/S23"));
+/// assert_eq!(macrocell::read_tree(&mc).unwrap().cells().unwrap(), tree.cells().unwrap());
+/// ```
+pub fn write_tree(tree: &QuadTree, rule: Option<&str>) -> String {
+    let mut meta = Pattern::default();
+    meta.set_rule(rule.map(str::to_string));
+    write_with(tree, &meta)
+}
+
+fn write_with(tree: &QuadTree, meta: &Pattern) -> String {
+    let mut out = "[M2] (ca)\n".to_string();
+    if let Some(rule) = meta.rule() {
+        out.push_str(&format!("#R {}\n", rule));
+    }
+    if let Some(name) = meta.name() {
+        out.push_str(&format!("#N {}\n", name));
+    }
+    for comment in meta.comments() {
+        out.push_str(&format!("#C {}\n", comment));
+    }
+
+    let multi_state = max_state(tree, tree.root(), &mut HashSet::new()) > 1;
+    let mut tree = tree.clone();
+    while tree.level() < if multi_state { 1 } else { 3 } {
+        tree.grow();
+    }
+    let mut writer = Writer {
+        tree: &tree,
+        multi_state,
+        numbers: HashMap::new(),
+        dead: HashMap::new(),
+        lines: Vec::new(),
+    };
+    writer.node(tree.root());
+    for line in writer.lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn max_state(tree: &QuadTree, id: usize, seen: &mut HashSet<usize>) -> u8 {
+    if !seen.insert(id) {
+        return 0;
+    }
+    match tree.node(id) {
+        Node::Leaf(s) => s.id(),
+        Node::Branch { children, .. } => children.iter().map(|c| max_state(tree, *c, seen)).max().unwrap_or(0),
+    }
+}
+
+/// Numbers nodes in the order they are written, children first
+struct Writer<'a> {
+    tree: &'a QuadTree,
+    multi_state: bool,
+    numbers: HashMap<usize, usize>,
+    /// Whether the nodes checked so far are all dead
+    dead: HashMap<usize, bool>,
+    lines: Vec<String>,
+}
+
+impl Writer<'_> {
+    /// Write a node and its children if they aren't written yet, returning
+    /// its number
+    fn node(&mut self, id: usize) -> usize {
+        if self.is_dead(id) {
+            return 0;
+        }
+        if let Some(n) = self.numbers.get(&id) {
+            return *n;
+        }
+        let Node::Branch { level, children } = self.tree.node(id) else {
+            unreachable!("leaves are written by their parent");
+        };
+        let line = if level == 1 && self.multi_state {
+            let states: Vec<_> = children.iter().map(|c| self.state(*c).to_string()).collect();
+            format!("1 {}", states.join(" "))
+        } else if level == 3 && !self.multi_state {
+            self.bitmap(id)
+        } else {
+            let numbers: Vec<_> = children.iter().map(|c| self.node(*c).to_string()).collect();
+            format!("{} {}", level, numbers.join(" "))
+        };
+        self.lines.push(line);
+        self.numbers.insert(id, self.lines.len());
+        self.lines.len()
+    }
+
+    /// Whether every cell of a node is in state 0
+    fn is_dead(&mut self, id: usize) -> bool {
+        if self.tree.is_empty_node(id) {
+            return true;
+        }
+        if let Some(dead) = self.dead.get(&id) {
+            return *dead;
+        }
+        let dead = match self.tree.node(id) {
+            Node::Leaf(s) => s.id() == 0,
+            Node::Branch { children, .. } => children.iter().all(|c| self.is_dead(*c)),
+        };
+        self.dead.insert(id, dead);
+        dead
+    }
+
+    fn state(&self, id: usize) -> u8 {
+        match self.tree.node(id) {
+            Node::Leaf(s) => s.id(),
+            Node::Branch { .. } => 0,
+        }
+    }
+
+    /// Rows of an 8x8 node, without trailing dead cells or empty rows
+    fn bitmap(&self, id: usize) -> String {
+        let rows: Vec<String> = (0..8)
+            .map(|y| {
+                let row: String = (0..8).map(|x| if self.cell(id, 3, x, y).id() == 0 { '.' } else { '*' }).collect();
+                row.trim_end_matches('.').to_string()
+            })
+            .collect();
+        let last = rows.iter().rposition(|r| !r.is_empty()).map_or(0, |r| r + 1);
+        rows[..last].iter().map(|r| format!("{}$", r)).collect()
+    }
+
+    fn cell(&self, id: usize, level: u32, x: usize, y: usize) -> State {
+        match self.tree.node(id) {
+            Node::Leaf(s) => s,
+            Node::Branch { children, .. } => {
+                let half = 1 << (level - 1);
+                let q = (y >= half) as usize * 2 + (x >= half) as usize;
+                self.cell(children[q], level - 1, x % half, y % half)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::point::Point;
+    use crate::pattern::rle;
+
+    #[test]
+    fn macrocell_test() {
+        let mc = "[M2] (ca)\n#R B3/S23\n.*$..*$***$\n4 1 1 1 1\n5 2 2 2 0\n";
+        let tree = read_tree(mc).unwrap();
+        assert_eq!((tree.level(), tree.population()), (5, 60));
+        assert_eq!(tree.get(Point::new(17, 8)), State::new(1));
+        assert_eq!(write_tree(&tree, Some("B3/S23")), mc);
+
+        let pattern = read(mc).unwrap();
+        assert_eq!((pattern.width(), pattern.height()), (27, 27));
+        assert_eq!(read(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn multi_state_macrocell_test() {
+        let pattern = rle::read("x = 4, y = 3\n.AB$pA$3.C!").unwrap();
+        let mc = write(&pattern);
+        assert_eq!(mc, "[M2] (ca)\n1 0 1 25 0\n1 2 0 0 0\n1 0 3 0 0\n2 1 2 0 3\n");
+        assert_eq!(read(&mc).unwrap().cells(), pattern.cells());
+    }
+
+    #[test]
+    fn built_tree_macrocell_test() {
+        // the dead quadrants are interned by `build` above the tree's level
+        let mut tree = QuadTree::new(0);
+        let root = tree.build(4, &|x, y| State::new((x >= 8 && y >= 8 && (x + y) % 3 == 0) as u8));
+        tree.set_root(root);
+        let Node::Branch { children, .. } = tree.node(root) else { panic!() };
+        assert!(tree.is_empty_node(children[0]));
+
+        let mc = write_tree(&tree, None);
+        assert_eq!(mc.lines().filter(|l| l.is_empty()).count(), 0);
+        assert!(mc.ends_with("\n4 0 0 0 1\n"));
+        assert_eq!(read_tree(&mc).unwrap().cells().unwrap(), tree.cells().unwrap());
+    }
+
+    #[test]
+    fn deep_macrocell_test() {
+        // a level 3 leaf nested in the north west and then south east corner
+        // of every level up to 40
+        let nested = |corner: &dyn Fn(usize) -> String| {
+            let nodes: String = (4..=40).map(|level| format!("{} {}\n", level, corner(level - 3))).collect();
+            format!("[M2] (golly 4.0)\n.*$\n{}", nodes)
+        };
+        let pattern = read(&nested(&|n| format!("{} 0 0 0", n))).unwrap();
+        assert_eq!((pattern.width(), pattern.height()), (1, 1));
+        let pattern = read(&nested(&|n| format!("0 0 0 {}", n))).unwrap();
+        assert_eq!(pattern.cells(), [(Point::new(0, 0), State::new(1))]);
+        assert!(read_tree(&nested(&|n| format!("0 0 0 {}", n))).unwrap().cells().is_err());
+    }
+
+    #[test]
+    fn macrocell_errors_test() {
+        assert_eq!(read("#R B3/S23\n").unwrap_err().line(), 1);
+        assert_eq!(read("[M2]\n4 1 0 0 0\n").unwrap_err().reason(), "node 1 isn't defined yet");
+        assert_eq!(read("[M2]\n.*$\n5 1 0 0 0\n").unwrap_err().reason(), "node 1 isn't of level 4");
+        assert!(read("[M2]\n1 0 0 0 256\n").is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! Extended RLE, the run length encoded pattern format used by Golly
+//!
+//! `#N`, `#O` and `#C` lines give the name, author and comments, followed by
+//! an `x = 3, y = 3, rule = B3/S23` header and runs of cells such as
+//! `bo$2bo$3o!`. Two state patterns use `b` and `o`, others use `.` for
+//! state 0 and `A` to `X` for 1 to 24, prefixed by `p` to `y` for higher
+//! states.
+
+use crate::errors::LoadError;
+use crate::grid::point::Point;
+use crate::pattern::Pattern;
+use crate::state::State;
+
+/// Longest line written
+pub const LINE_WIDTH: usize = 70;
+
+/// Read an RLE file
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::pattern::rle;
+/// use ca::state::State;
+///
+/// let glider = rle::read("#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+/// assert_eq!((glider.width(), glider.height()), (3, 3));
+/// assert_eq!(glider.rule(), Some("B3/S23"));
+/// assert_eq!(glider.cells().len(), 5);
+/// assert_eq!(glider.get(Point::new(2, 1)), State::new(1));
+/// ```
+pub fn read(rle: &str) -> Result<Pattern, LoadError> {
+    let mut pattern = Pattern::default();
+    let mut has_header = false;
+    let (mut x, mut y) = (0usize, 0usize);
+    let mut count: Option<usize> = None;
+    let mut prefix: Option<char> = None;
+    let mut last_line = 0;
+
+    'lines: for (i, line) in rle.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |reason: String| LoadError::new(line_no, reason);
+        let line = line.trim();
+        last_line = line_no;
+        if !has_header {
+            if let Some(comment) = line.strip_prefix('#') {
+                let (tag, text) = comment.split_at(comment.chars().next().map_or(0, char::len_utf8));
+                let text = text.trim().to_string();
+                match tag {
+                    "N" => pattern.name = Some(text),
+                    "O" => pattern.author = Some(text),
+                    "C" | "c" => pattern.comments.push(text),
+                    "r" => pattern.rule = Some(text),
+                    _ => {}
+                }
+            } else if !line.is_empty() {
+                header(line, &mut pattern).map_err(err)?;
+                has_header = true;
+            }
+            continue;
+        }
+
+        for c in line.chars() {
+            let run = count.unwrap_or(1);
+            match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap() as usize;
+                    count = count
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(digit))
+                        .map(Some)
+                        .ok_or_else(|| err("run length is too long".to_string()))?;
+                    continue;
+                }
+                'p'..='y' if prefix.is_none() => {
+                    prefix = Some(c);
+                    continue;
+                }
+                'b' | '.' | 'o' | 'A'..='X' => {
+                    let state = state(prefix, c).ok_or_else(|| err(format!("invalid state {}{}", prefix.map_or(String::new(), String::from), c)))?;
+                    let end = x.checked_add(run).filter(|end| *end <= pattern.width && y < pattern.height);
+                    let end = end.ok_or_else(|| err(outside(&pattern)))?;
+                    if state.id() != 0 {
+                        pattern.cells.extend((x..end).map(|x| (Point::new(x as i32, y as i32), state)));
+                    }
+                    x = end;
+                }
+                '$' => {
+                    y = y.checked_add(run).filter(|y| *y <= pattern.height).ok_or_else(|| err(outside(&pattern)))?;
+                    x = 0;
+                }
+                '!' => break 'lines,
+                c if c.is_whitespace() => continue,
+                c => return Err(err(format!("unexpected {:?}", c))),
+            }
+            if let Some(p) = prefix.filter(|_| !c.is_ascii_uppercase()) {
+                return Err(err(format!("{:?} must be followed by a state", p)));
+            }
+            count = None;
+            prefix = None;
+        }
+    }
+
+    if !has_header {
+        return Err(LoadError::new(last_line.max(1), "missing x = .., y = .. header"));
+    }
+    Ok(pattern)
+}
+
+fn outside(pattern: &Pattern) -> String {
+    format!("cells outside of the {}x{} bounding box", pattern.width, pattern.height)
+}
+
+/// Read the `x = .., y = .., rule = ..` header. The rule is the rest of the
+/// line since it may contain commas
+fn header(line: &str, pattern: &mut Pattern) -> Result<(), String> {
+    let mut rest = line;
+    let (mut width, mut height) = (None, None);
+    while !rest.trim().is_empty() {
+        let (key, after) = rest.split_once('=').ok_or_else(|| format!("invalid header {:?}", line))?;
+        let key = key.trim();
+        if key == "rule" {
+            pattern.rule = Some(after.trim().to_string());
+            break;
+        }
+        let (value, next) = after.split_once(',').unwrap_or((after, ""));
+        // cells are placed at i32 coordinates
+        let size = || {
+            let size = value.trim().parse::<usize>().ok().filter(|s| *s <= i32::MAX as usize);
+            size.ok_or_else(|| format!("invalid {} {:?}", key, value.trim()))
+        };
+        match key {
+            "x" => width = Some(size()?),
+            "y" => height = Some(size()?),
+            _ => {}
+        }
+        rest = next;
+    }
+    pattern.width = width.ok_or("header has no x")?;
+    pattern.height = height.ok_or("header has no y")?;
+    Ok(())
+}
+
+/// State of a cell tag, with its optional `p` to `y` prefix
+fn state(prefix: Option<char>, tag: char) -> Option<State> {
+    let id = match (prefix, tag) {
+        (None, 'b' | '.') => 0,
+        (None, 'o') => 1,
+        (prefix, 'A'..='X') => {
+            let high = prefix.map_or(0, |p| p as u32 - 'p' as u32 + 1);
+            high * 24 + tag as u32 - 'A' as u32 + 1
+        }
+        _ => return None,
+    };
+    u8::try_from(id).ok().map(State::new)
+}
+
+/// Tag of a state, `b` and `o` are used for two state patterns
+fn tag(state: State, multi_state: bool) -> String {
+    match (state.id(), multi_state) {
+        (0, false) => "b".to_string(),
+        (1, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (id, _) => {
+            let (high, low) = ((id - 1) / 24, (id - 1) % 24);
+            let letter = (b'A' + low) as char;
+            match high {
+                0 => letter.to_string(),
+                h => format!("{}{}", (b'p' + h - 1) as char, letter),
+            }
+        }
+    }
+}
+
+/// Write a pattern as RLE, wrapping lines at [`LINE_WIDTH`] columns
+///
+/// # Examples
+///
+/// ```
+/// use ca::pattern::rle;
+///
+/// let glider = rle::read("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+/// assert_eq!(rle::write(&glider), "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+/// ```
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if let Some(name) = pattern.name() {
+        out.push_str(&format!("#N {}\n", name));
+    }
+    if let Some(author) = pattern.author() {
+        out.push_str(&format!("#O {}\n", author));
+    }
+    for comment in pattern.comments() {
+        out.push_str(&format!("#C {}\n", comment));
+    }
+    out.push_str(&format!("x = {}, y = {}", pattern.width(), pattern.height()));
+    if let Some(rule) = pattern.rule() {
+        out.push_str(&format!(", rule = {}", rule));
+    }
+    out.push('\n');
+
+    let multi_state = pattern.max_state().id() > 1;
+    let token = |n: usize, tag: String| if n > 1 { format!("{}{}", n, tag) } else { tag };
+    let mut tokens = Vec::new();
+    let mut rows_ended = 0;
+    for row in pattern.to_rows().chunks(pattern.width().max(1)) {
+        let mut runs: Vec<(State, usize)> = Vec::new();
+        for cell in row {
+            match runs.last_mut() {
+                Some((s, n)) if s == cell => *n += 1,
+                _ => runs.push((*cell, 1)),
+            }
+        }
+        if runs.last().is_some_and(|(s, _)| s.id() == 0) {
+            runs.pop();
+        }
+        if !runs.is_empty() {
+            if rows_ended > 0 {
+                tokens.push(token(rows_ended, "$".to_string()));
+            }
+            tokens.extend(runs.into_iter().map(|(s, n)| token(n, tag(s, multi_state))));
+            rows_ended = 0;
+        }
+        rows_ended += 1;
+    }
+    tokens.push("!".to_string());
+
+    let mut line = String::new();
+    for t in tokens {
+        if line.len() + t.len() > LINE_WIDTH {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(&t);
+    }
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::dense_grid::DenseGrid;
+
+    #[test]
+    fn rle_round_trip_test() {
+        let file = "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship\n\
+                    x = 3, y = 4, rule = B3/S23:T10,20\nbo$2bo$3o!\n";
+        let pattern = read(file).unwrap();
+        assert_eq!(pattern.name(), Some("Glider"));
+        assert_eq!(pattern.author(), Some("Richard K. Guy"));
+        assert_eq!(pattern.comments(), &["The smallest spaceship".to_string()]);
+        assert_eq!(pattern.rule(), Some("B3/S23:T10,20"));
+        assert_eq!(write(&pattern), file);
+    }
+
+    #[test]
+    fn multi_state_test() {
+        let pattern = read("x = 5, y = 3\n.A2B2$\n3.pAyO!").unwrap();
+        let states: Vec<_> = pattern.cells().iter().map(|(_, s)| s.id()).collect();
+        assert_eq!(states, vec![1, 2, 2, 25, 255]);
+        assert_eq!(pattern.get(Point::new(4, 2)), State::new(255));
+        assert_eq!(write(&pattern), "x = 5, y = 3\n.A2B2$3.pAyO!\n");
+        assert_eq!(read(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn wrap_test() {
+        let mut grid = DenseGrid::new(200, 3);
+        for x in (0..200).step_by(2) {
+            grid.set(Point::new(x, 1), State::new(1));
+        }
+        let pattern = Pattern::from_grid(&grid, Point::new(0, 0), 200, 3);
+        let rle = write(&pattern);
+        assert!(rle.lines().all(|l| l.len() <= LINE_WIDTH));
+        assert!(rle.lines().count() > 3);
+        assert_eq!(read(&rle).unwrap().cells(), pattern.cells());
+    }
+
+    #[test]
+    fn rle_errors_test() {
+        assert_eq!(read("bo$2bo$3o!").unwrap_err().reason(), "invalid header \"bo$2bo$3o!\"");
+        assert_eq!(read("#C only comments").unwrap_err().line(), 1);
+        assert_eq!(read("x = 2, y = 1\n3o!").unwrap_err().line(), 2);
+        assert_eq!(read("x = 2, y = 1\nbz!").unwrap_err().reason(), "unexpected 'z'");
+        assert!(read("x = 2, y = 1\npb!").is_err());
+        // runs too long for an i32 don't wrap or overflow
+        assert_eq!(read("x = 3, y = 1\n4294967295o2o!").unwrap_err().reason(), "cells outside of the 3x1 bounding box");
+        assert_eq!(read("x = 1, y = 1\n$2147483647$!").unwrap_err().reason(), "cells outside of the 1x1 bounding box");
+        assert!(read("x = 4294967296, y = 1\no!").is_err());
+    }
+}
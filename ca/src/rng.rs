@@ -0,0 +1,135 @@
+//! Seeded pseudo random numbers for stochastic rules. Every cell gets its own
+//! stream derived from the automaton's seed, its coordinates and the
+//! generation, so the order cells are updated in doesn't change the result
+
+use crate::grid::point::{IsPoint, Point};
+
+/// Step of the SplitMix64 generator, used to expand seeds
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// xoshiro256** generator
+///
+/// # Examples
+///
+/// ```
+/// use ca::grid::point::Point;
+/// use ca::rng::Rng;
+///
+/// let mut a = Rng::for_cell(42, Point::new(3, 4), 10);
+/// let mut b = Rng::for_cell(42, Point::new(3, 4), 10);
+/// assert_eq!(a.next_u64(), b.next_u64());
+/// assert!(a.next_f64() < 1.0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    /// Return a new `Rng` whose state is expanded from `seed`
+    pub fn new(seed: u64) -> Rng {
+        let mut sm = seed;
+        Rng {
+            s: [
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+            ],
+        }
+    }
+
+    /// Stream for the cell at `point` during `generation`
+    pub fn for_cell(seed: u64, point: Point<i32>, generation: u64) -> Rng {
+        let mut key = ((point.x() as u32 as u64) << 32) | point.y() as u32 as u64;
+        let mut gen = generation;
+        Rng::new(seed ^ splitmix64(&mut key) ^ splitmix64(&mut gen).rotate_left(17))
+    }
+
+    /// Stream shared by every cell during `generation`, e.g. to pick the
+    /// order they update in
+    pub fn for_step(seed: u64, generation: u64) -> Rng {
+        let mut gen = generation;
+        Rng::new(!seed ^ splitmix64(&mut gen))
+    }
+
+    /// Restore a generator from [`Rng::state`]. `None` if the state is all
+    /// zero, which xoshiro can't leave
+    pub fn from_state(s: [u64; 4]) -> Option<Rng> {
+        (s != [0; 4]).then_some(Rng { s })
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.s
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform number in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform number in `0..n`, `n` must not be 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        // reject the top values which would make lower results more likely
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return v % n;
+            }
+        }
+    }
+
+    /// Return true with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xoshiro_test() {
+        // reference output of xoshiro256** from the state 1, 2, 3, 4
+        let mut rng = Rng::from_state([1, 2, 3, 4]).unwrap();
+        let out: Vec<_> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(out, vec![11520, 0, 1509978240]);
+        assert!(Rng::from_state([0; 4]).is_none());
+    }
+
+    #[test]
+    fn streams_test() {
+        let first = |p, g| Rng::for_cell(7, p, g).next_u64();
+        let origin = Point::new(0, 0);
+        assert_ne!(first(origin, 0), first(Point::new(1, 0), 0));
+        assert_ne!(first(origin, 0), first(Point::new(0, 1), 0));
+        assert_ne!(first(origin, 0), first(origin, 1));
+        assert_ne!(Rng::for_cell(7, origin, 0), Rng::for_cell(8, origin, 0));
+
+        let mut rng = Rng::new(1);
+        let hits = (0..10_000).filter(|_| rng.chance(0.25)).count();
+        assert!((2300..2700).contains(&hits));
+        assert!((0..1000).all(|_| rng.below(6) < 6));
+    }
+}
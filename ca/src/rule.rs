@@ -1,28 +1,61 @@
-use std::{collections::HashMap, ops::{Add, Mul}};
+pub mod builder;
+pub mod lookup;
+pub mod reduce;
 
-use crate::{grid::apoint::APoint, state::State};
-use crate::errors::SumError;
+use std::{collections::HashMap, hash::Hash, ops::{Add, Mul}};
 
-/// Function that maps one state to another based on
-/// its implementation
-pub struct RuleFunc<T>(fn(State<T>) -> State<T>);
+use crate::{grid::{apoint::APoint, point::Point}, state::State};
+use crate::errors::CaError;
+use crate::rng::Rng;
 
-/// A Rule must be able to use a function to map one state to another
-pub trait Rule<T> {
-    fn apply(&self, func: RuleFunc<State<T>>) -> State<T>;
+/// A Rule determines the state of a cell at the next step
+pub trait Rule {
+    /// Offsets of the neighbours relative to the cell, in the order they are
+    /// passed to [`Rule::apply`]
+    fn neighbourhood(&self) -> &[Point<i32>];
+
+    /// Return the next state of the cell at `point` in `state` surrounded by
+    /// `neighbours`, at step `generation`. Stochastic rules draw from `rng`,
+    /// the cell's own stream for this step
+    fn apply(&self, state: State, neighbours: &[State], point: Point<i32>, generation: u64, rng: &mut Rng) -> State;
+
+    /// Apply the rule to a cell at the origin in generation 0, which is all
+    /// a local rule needs
+    fn next_state(&self, state: State, neighbours: &[State]) -> State {
+        self.apply(state, neighbours, Point::new(0, 0), 0, &mut Rng::new(0))
+    }
+
+    /// Number of states, cells are in states with ids `0..n_states`
+    fn n_states(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the next state depends only on the states of the cell and its
+    /// neighbours. Local rules that know their number of states are compiled
+    /// into a [`lookup::LookupTable`]
+    fn is_local(&self) -> bool {
+        false
+    }
 }
 
 /// Mapping of each state to the function that determines the state of cell
-/// at next step
+/// at next step from the states of its neighbours. A state without a function
+/// stays the same
 pub struct Rules<T, U>
     where
-    U: Fn(T) -> T {
+    U: Fn(&[T]) -> T {
         rules: HashMap<T, U>,
+        neighbourhood: Vec<Point<i32>>,
+        n_states: Option<usize>,
     }
 
+/// [`Rules`] built at runtime, e.g. compiled from a CA description
+pub type DynRules = Rules<State, Box<dyn Fn(&[State]) -> State + Send + Sync>>;
+
 impl<T, U> Rules<T, U>
 where
-    U: Fn(T) -> T {
+    T: Eq + Hash,
+    U: Fn(&[T]) -> T {
 
     /// Return a new, empty `Rules` struct
     ///
@@ -30,12 +63,15 @@ where
     ///
     /// ```
     /// use ca::rule::*;
-    /// let rules: Rules<i32, fn(i32) -> i32> = Rules::new();
+    /// use ca::state::State;
+    /// let rules: Rules<State, fn(&[State]) -> State> = Rules::new();
     ///
     /// ```
     pub fn new() -> Rules<T, U> {
         Rules {
             rules: HashMap::new(),
+            neighbourhood: Vec::new(),
+            n_states: None,
         }
     }
 
@@ -45,14 +81,76 @@ where
     ///
     /// ```
     /// use ca::rule::*;
-    /// let mut rules: Rules<i32, fn(i32) ->i32> = Rules::new();
-    /// let r = Rule::create_rules("RULES");
+    /// use ca::state::State;
+    /// use std::collections::HashMap;
+    /// let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+    /// let mut r: HashMap<State, fn(&[State]) -> State> = HashMap::new();
+    /// r.insert(State::new(1), |n| n.iter().copied().max().unwrap_or_default());
     /// rules.set_rules(r);
     ///
     /// ```
     pub fn set_rules(&mut self, rule_set: HashMap<T, U>) {
         self.rules = rule_set;
     }
+
+    /// Set the function that determines the next state of cells in `state`
+    pub fn insert(&mut self, state: T, func: U) {
+        self.rules.insert(state, func);
+    }
+
+    /// Get the function for cells in `state`
+    pub fn get(&self, state: &T) -> Option<&U> {
+        self.rules.get(state)
+    }
+
+    /// Set the offsets of the neighbours passed to each function
+    pub fn set_neighbourhood(&mut self, neighbourhood: Vec<Point<i32>>) {
+        self.neighbourhood = neighbourhood;
+    }
+
+    /// Set the number of states cells can be in, needed to compile the rules
+    /// into a lookup table
+    pub fn set_n_states(&mut self, n_states: Option<usize>) {
+        self.n_states = n_states;
+    }
+}
+
+impl<T, U> Default for Rules<T, U>
+where
+    T: Eq + Hash,
+    U: Fn(&[T]) -> T {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U> Rule for Rules<State, U>
+where
+    U: Fn(&[State]) -> State {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+        self.rules.get(&state).map_or(state, |f| f(neighbours))
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        self.n_states
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// Call [`Rule::next_state`] with states given by their ids
+#[cfg(test)]
+pub(crate) fn next_ids<R>(rules: &R, state: u8, neighbours: &[u8]) -> u8
+where
+    R: Rule + ?Sized, {
+    let neighbours: Vec<State> = neighbours.iter().map(|n| State::new(*n)).collect();
+    rules.next_state(State::new(state), &neighbours).id()
 }
 
 // TODO
@@ -65,21 +163,22 @@ where
 // }
 
 
-/// Implement trait to more easily execute more complex summations 
+/// Implement trait to more easily execute more complex summations. Rules
+/// reduce the neighbours they are given with [`reduce::Reduce`]
 pub trait Sum<T> {
-    fn sum(&self) -> Result<T, SumError>;
+    fn sum(&self) -> Result<T, CaError>;
 }
 
-impl<'a, T> Sum<i32> for APoint<'a, T, State<'a, i32>>
+impl<'a, T> Sum<i32> for APoint<'a, T>
 where
     T: Copy + Ord + PartialEq + Add<Output = T> + Mul<Output = T>, {
-    fn sum(&self) -> Result<i32, SumError> {
+    fn sum(&self) -> Result<i32, CaError> {
         let mut s = 0;
         for n in self.neighbours(){
             let v = n.cur_state();
             s += match v {
-                Some(i) => *i.val().val(),
-                None => return Err(SumError),
+                Some(i) => i.id() as i32,
+                None => return Err(CaError::MissingState("a neighbour has no state to sum".into())),
             }
         }
         Ok(s)
@@ -0,0 +1,322 @@
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+use crate::errors::CaError;
+use crate::grid::neighbourhood::{relative_neighbourhood_coords, NType};
+use crate::grid::point::Point;
+use crate::rule::reduce::Reduce;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::{State, StateSet};
+
+/// A state given by its name, resolved when the rules are built, or directly
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateRef {
+    Name(String),
+    State(State),
+}
+
+impl StateRef {
+    fn resolve(&self, states: &StateSet) -> Result<State, CaError> {
+        match self {
+            StateRef::Name(name) => states
+                .get(name)
+                .ok_or_else(|| CaError::MissingState(format!("undeclared state '{}'", name))),
+            StateRef::State(state) => Ok(*state),
+        }
+    }
+}
+
+impl From<State> for StateRef {
+    fn from(state: State) -> Self {
+        StateRef::State(state)
+    }
+}
+
+impl From<&str> for StateRef {
+    fn from(name: &str) -> Self {
+        StateRef::Name(name.to_string())
+    }
+}
+
+/// Refer to a state by name
+pub fn state(name: &str) -> StateRef {
+    StateRef::Name(name.to_string())
+}
+
+/// A number computed from the neighbours
+#[derive(Clone, Debug)]
+enum Measure<S> {
+    Count(Vec<S>),
+    Sum,
+}
+
+#[derive(Clone, Debug)]
+struct Condition<S> {
+    measure: Measure<S>,
+    range: RangeInclusive<i64>,
+}
+
+#[derive(Clone, Debug)]
+struct Transition<S> {
+    from: S,
+    conditions: Vec<Condition<S>>,
+    probability: f64,
+    to: S,
+}
+
+/// Builder of a [`RuleSet`] from transitions between states, each taken
+/// when every one of its conditions on the neighbours holds
+///
+/// # Examples
+///
+/// ```
+/// use ca::automaton::Automaton2D;
+/// use ca::grid::point::Point;
+/// use ca::rule::builder::{state, RuleBuilder};
+/// use ca::state::StateSet;
+///
+/// let mut states = StateSet::new();
+/// let dead = states.push("dead").unwrap();
+/// let alive = states.push("alive").unwrap();
+/// let life = RuleBuilder::new()
+///     .when(state("dead")).count(state("alive")).equals(3).then(state("alive"))
+///     .when(state("alive")).count(state("alive")).in_range(2..=3).then(state("alive"))
+///     .when(state("alive")).then(state("dead"))
+///     .build(&states)
+///     .unwrap();
+///
+/// let mut automaton = Automaton2D::new(5, 5, life.clone());
+/// automaton.set_rules(life);
+/// automaton.set_seed(&[(Point::new(1, 2), alive), (Point::new(2, 2), alive), (Point::new(3, 2), alive)]).unwrap();
+/// automaton.step();
+/// assert_eq!(automaton.grid().get(Point::new(2, 1)), Some(&alive));
+/// assert_eq!(automaton.grid().get(Point::new(1, 2)), Some(&dead));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuleBuilder {
+    neighbourhood: Vec<Point<i32>>,
+    rules: Vec<Transition<StateRef>>,
+}
+
+impl RuleBuilder {
+    /// Return a new `RuleBuilder` with no transitions and the Moore
+    /// neighbourhood
+    pub fn new() -> RuleBuilder {
+        RuleBuilder {
+            neighbourhood: relative_neighbourhood_coords(NType::Moore, None).unwrap_or_default(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Set the offsets of the neighbours the conditions are checked on
+    pub fn neighbourhood(mut self, neighbourhood: Vec<Point<i32>>) -> RuleBuilder {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    /// Start a transition out of `from`
+    pub fn when(self, from: impl Into<StateRef>) -> When {
+        When {
+            builder: self,
+            from: from.into(),
+            conditions: Vec::new(),
+            probability: 1.0,
+        }
+    }
+
+    /// Resolve every state name, fails if one isn't in `states`
+    pub fn build(self, states: &StateSet) -> Result<RuleSet, CaError> {
+        let mut rules = Vec::new();
+        for rule in self.rules {
+            let mut conditions = Vec::new();
+            for c in rule.conditions {
+                let measure = match c.measure {
+                    Measure::Count(s) => Measure::Count(s.iter().map(|s| s.resolve(states)).collect::<Result<_, _>>()?),
+                    Measure::Sum => Measure::Sum,
+                };
+                conditions.push(Condition {
+                    measure,
+                    range: c.range,
+                });
+            }
+            rules.push(Transition {
+                from: rule.from.resolve(states)?,
+                conditions,
+                probability: rule.probability,
+                to: rule.to.resolve(states)?,
+            });
+        }
+        Ok(RuleSet {
+            neighbourhood: self.neighbourhood,
+            rules,
+            n_states: Some(states.n_states()).filter(|n| *n > 0),
+        })
+    }
+}
+
+impl Default for RuleBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A transition being built, see [`RuleBuilder`]
+#[derive(Clone, Debug)]
+pub struct When {
+    builder: RuleBuilder,
+    from: StateRef,
+    conditions: Vec<Condition<StateRef>>,
+    probability: f64,
+}
+
+impl When {
+    /// Condition on the number of neighbours in `state`
+    pub fn count(self, state: impl Into<StateRef>) -> Compare {
+        self.count_any([state])
+    }
+
+    /// Condition on the number of neighbours in any of `states`
+    pub fn count_any<S>(self, states: impl IntoIterator<Item = S>) -> Compare
+    where
+        S: Into<StateRef>, {
+        Compare {
+            when: self,
+            measure: Measure::Count(states.into_iter().map(|s| s.into()).collect()),
+        }
+    }
+
+    /// Condition on the sum of the neighbours' state ids
+    pub fn sum(self) -> Compare {
+        Compare {
+            when: self,
+            measure: Measure::Sum,
+        }
+    }
+
+    /// Only take the transition with probability `p` when its conditions
+    /// hold, drawing from the cell's random stream
+    pub fn with_probability(mut self, p: f64) -> When {
+        self.probability = p;
+        self
+    }
+
+    /// Finish the transition, cells matching it go to `to`
+    pub fn then(mut self, to: impl Into<StateRef>) -> RuleBuilder {
+        self.builder.rules.push(Transition {
+            from: self.from,
+            conditions: self.conditions,
+            probability: self.probability,
+            to: to.into(),
+        });
+        self.builder
+    }
+}
+
+/// A condition missing the values it accepts, see [`RuleBuilder`]
+#[derive(Clone, Debug)]
+pub struct Compare {
+    when: When,
+    measure: Measure<StateRef>,
+}
+
+impl Compare {
+    pub fn in_range(mut self, range: impl RangeBounds<i64>) -> When {
+        let start = match range.start_bound() {
+            Bound::Included(s) => *s,
+            Bound::Excluded(s) => s.saturating_add(1),
+            Bound::Unbounded => i64::MIN,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => *e,
+            Bound::Excluded(e) => e.saturating_sub(1),
+            Bound::Unbounded => i64::MAX,
+        };
+        self.when.conditions.push(Condition {
+            measure: self.measure,
+            range: start..=end,
+        });
+        self.when
+    }
+
+    pub fn equals(self, value: i64) -> When {
+        self.in_range(value..=value)
+    }
+
+    pub fn at_least(self, value: i64) -> When {
+        self.in_range(value..)
+    }
+
+    pub fn at_most(self, value: i64) -> When {
+        self.in_range(..=value)
+    }
+}
+
+/// Rules built by a [`RuleBuilder`]. The first transition out of a cell's
+/// state whose conditions all hold, and whose random draw succeeds if it has
+/// a probability, gives its next state. A cell with no such transition keeps
+/// its state
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    neighbourhood: Vec<Point<i32>>,
+    rules: Vec<Transition<State>>,
+    n_states: Option<usize>,
+}
+
+impl Rule for RuleSet {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, rng: &mut Rng) -> State {
+        self.rules
+            .iter()
+            .filter(|r| r.from == state)
+            .find(|r| {
+                r.conditions.iter().all(|c| {
+                    let value = match &c.measure {
+                        Measure::Count(states) => neighbours.count_any(states) as i64,
+                        Measure::Sum => neighbours.sum(),
+                    };
+                    c.range.contains(&value)
+                }) && (r.probability >= 1.0 || rng.chance(r.probability))
+            })
+            .map_or(state, |r| r.to)
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        self.n_states
+    }
+
+    fn is_local(&self) -> bool {
+        self.rules.iter().all(|r| r.probability >= 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::next_ids;
+
+    #[test]
+    fn builder_test() {
+        let mut states = StateSet::new();
+        for name in ["off", "on", "dying"] {
+            states.push(name);
+        }
+        let rules = RuleBuilder::new()
+            .neighbourhood(vec![Point::new(-1, 0), Point::new(1, 0), Point::new(0, 1)])
+            .when(state("off")).count_any([state("on"), state("dying")]).at_least(2).sum().at_most(3).then(state("on"))
+            .when("on").then(State::new(2))
+            .build(&states)
+            .unwrap();
+        assert_eq!(rules.n_states(), Some(3));
+        assert_eq!(next_ids(&rules, 0, &[1, 1, 0]), 1);
+        assert_eq!(next_ids(&rules, 0, &[2, 2, 0]), 0);
+        assert_eq!(next_ids(&rules, 0, &[1, 0, 0]), 0);
+        assert_eq!(next_ids(&rules, 1, &[0, 0, 0]), 2);
+        assert_eq!(next_ids(&rules, 2, &[1, 1, 1]), 2);
+
+        let err = RuleBuilder::new().when(state("off")).count(state("of")).equals(1).then("on").build(&states);
+        assert_eq!(err.unwrap_err().to_string(), "missing state: undeclared state 'of'");
+    }
+}
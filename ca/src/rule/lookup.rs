@@ -0,0 +1,169 @@
+use crate::grid::point::Point;
+use crate::rule::Rule;
+use crate::rng::Rng;
+use crate::state::State;
+
+/// Largest number of entries a [`LookupTable`] is compiled with, rules with
+/// more inputs than this fall back to calling the rule for every cell
+pub const MAX_TABLE_SIZE: usize = 1 << 22;
+
+/// Every next state of a [`Rule`] enumerated into one flat table,
+/// indexed by the cell's state followed by its neighbours' states as the
+/// digits of a base `n_states` number
+///
+/// # Examples
+///
+/// ```
+/// use ca::golly::rule_table::RuleTable;
+/// use ca::rule::lookup::LookupTable;
+/// use ca::rule::Rule;
+/// use ca::state::State;
+///
+/// let rule = RuleTable::parse("n_states:2\nneighborhood:vonNeumann\n0,1,0,0,0,1\n").unwrap();
+/// let table = LookupTable::compile(&rule).unwrap();
+/// assert_eq!(table.len(), 32);
+/// let [off, on] = [State::new(0), State::new(1)];
+/// assert_eq!(table.next_state(off, &[on, off, off, off]), on);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LookupTable {
+    n_states: usize,
+    neighbourhood: Vec<Point<i32>>,
+    table: Vec<State>,
+}
+
+impl LookupTable {
+    /// Enumerate every input of `rules`. `None` if the rules aren't local,
+    /// don't know how many states they have or the table would be larger than
+    /// [`MAX_TABLE_SIZE`]
+    pub fn compile<R>(rules: &R) -> Option<LookupTable>
+    where
+        R: Rule + ?Sized, {
+        if !rules.is_local() {
+            return None;
+        }
+        let n_states = rules.n_states().filter(|n| (1..=256).contains(n))?;
+        let neighbourhood = rules.neighbourhood().to_vec();
+        let mut size: usize = 1;
+        for _ in 0..=neighbourhood.len() {
+            size = size.checked_mul(n_states).filter(|s| *s <= MAX_TABLE_SIZE)?;
+        }
+
+        // digits of the current index, the cell's state first
+        let mut digits = vec![State::default(); neighbourhood.len() + 1];
+        let mut table = Vec::with_capacity(size);
+        for _ in 0..size {
+            table.push(rules.next_state(digits[0], &digits[1..]));
+            for d in digits.iter_mut().rev() {
+                let next = d.id() as usize + 1;
+                if next < n_states {
+                    *d = State::new(next as u8);
+                    break;
+                }
+                *d = State::default();
+            }
+        }
+
+        Some(LookupTable {
+            n_states,
+            neighbourhood,
+            table,
+        })
+    }
+
+    /// Number of entries in the table
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Return the next state, `None` if any state is outside of the table
+    pub fn get(&self, state: State, neighbours: &[State]) -> Option<State> {
+        let n = self.n_states;
+        let mut index = state.id() as usize;
+        if index >= n || neighbours.len() != self.neighbourhood.len() {
+            return None;
+        }
+        for s in neighbours {
+            let s = s.id() as usize;
+            if s >= n {
+                return None;
+            }
+            index = index * n + s;
+        }
+        Some(self.table[index])
+    }
+}
+
+impl Rule for LookupTable {
+    fn neighbourhood(&self) -> &[Point<i32>] {
+        &self.neighbourhood
+    }
+
+    fn apply(&self, state: State, neighbours: &[State], _point: Point<i32>, _generation: u64, _rng: &mut Rng) -> State {
+        self.get(state, neighbours).unwrap_or(state)
+    }
+
+    fn n_states(&self) -> Option<usize> {
+        Some(self.n_states)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rules;
+
+    #[test]
+    fn compile_matches_rules_test() {
+        let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+        rules.insert(State::new(0), |n| State::new((n[0].id() + n[1].id() * 2) % 3));
+        rules.insert(State::new(2), |n| n[1]);
+        rules.set_neighbourhood(vec![Point::new(-1, 0), Point::new(1, 0)]);
+        rules.set_n_states(Some(3));
+
+        let table = LookupTable::compile(&rules).unwrap();
+        assert_eq!(table.len(), 27);
+        let states = [0, 1, 2].map(State::new);
+        for s in states {
+            for a in states {
+                for b in states {
+                    assert_eq!(table.next_state(s, &[a, b]), rules.next_state(s, &[a, b]));
+                }
+            }
+        }
+        let [zero, three, five] = [0, 3, 5].map(State::new);
+        assert_eq!(table.get(three, &[zero, zero]), None);
+        assert_eq!(table.get(zero, &[zero, five]), None);
+    }
+
+    #[test]
+    fn too_large_test() {
+        let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+        rules.set_neighbourhood(vec![Point::new(0, 0); 8]);
+        rules.set_n_states(Some(8));
+        assert!(LookupTable::compile(&rules).is_none());
+        rules.set_n_states(None);
+        assert!(LookupTable::compile(&rules).is_none());
+    }
+
+    #[test]
+    fn all_states_test() {
+        // the digits count up to the last of 256 states without overflowing
+        let mut rules: Rules<State, fn(&[State]) -> State> = Rules::new();
+        rules.insert(State::new(255), |n| n[0]);
+        rules.set_neighbourhood(vec![Point::new(1, 0)]);
+        rules.set_n_states(Some(256));
+        let table = LookupTable::compile(&rules).unwrap();
+        assert_eq!(table.len(), 256 * 256);
+        assert_eq!(table.get(State::new(255), &[State::new(254)]), Some(State::new(254)));
+        assert_eq!(table.get(State::new(254), &[State::new(255)]), Some(State::new(254)));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::state::State;
+
+/// Number of cells in each state
+///
+/// # Examples
+///
+/// ```
+/// use ca::rule::reduce::Reduce;
+/// use ca::state::State;
+///
+/// let neighbours = [1, 0, 2, 1].map(State::new);
+/// let histogram = neighbours.histogram();
+/// assert_eq!(histogram.get(State::new(1)), 2);
+/// assert_eq!(histogram.most_common(), Some(State::new(1)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Histogram([u32; 256]);
+
+impl Histogram {
+    /// Number of cells in `state`
+    pub fn get(&self, state: State) -> u32 {
+        self.0[state.id() as usize]
+    }
+
+    /// State with the most cells, the lowest one on a tie. `None` if there
+    /// are no cells
+    pub fn most_common(&self) -> Option<State> {
+        let (id, count) = self.0.iter().enumerate().rev().max_by_key(|(_, c)| **c)?;
+        (*count > 0).then_some(State::new(id as u8))
+    }
+
+    /// Iterate over the states with at least one cell and their counts
+    pub fn iter(&self) -> impl Iterator<Item = (State, u32)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(id, c)| (State::new(id as u8), *c))
+    }
+}
+
+/// Reductions over the states of a cell's neighbours, in the order of the
+/// rule's neighbourhood. None of them allocate, so they can be used inside
+/// rule functions
+pub trait Reduce {
+    fn states(&self) -> &[State];
+
+    /// State of the neighbour at `index` in the neighbourhood
+    fn nth(&self, index: usize) -> Option<State> {
+        self.states().get(index).copied()
+    }
+
+    /// Number of neighbours in `state`
+    fn count(&self, state: State) -> usize {
+        self.states().iter().filter(|s| **s == state).count()
+    }
+
+    /// Number of neighbours in any of `states`
+    fn count_any(&self, states: &[State]) -> usize {
+        self.states().iter().filter(|s| states.contains(s)).count()
+    }
+
+    fn histogram(&self) -> Histogram {
+        let mut histogram = Histogram([0; 256]);
+        for s in self.states() {
+            histogram.0[s.id() as usize] += 1;
+        }
+        histogram
+    }
+
+    /// Lowest neighbouring state, `None` if there are no neighbours
+    fn min_state(&self) -> Option<State> {
+        self.states().iter().min().copied()
+    }
+
+    /// Highest neighbouring state, `None` if there are no neighbours
+    fn max_state(&self) -> Option<State> {
+        self.states().iter().max().copied()
+    }
+
+    /// Sum of the state ids
+    fn sum(&self) -> i64 {
+        self.states().iter().map(|s| s.id() as i64).sum()
+    }
+
+    /// Sum of the state ids each multiplied by the weight at the same index.
+    /// Neighbours without a weight are ignored
+    fn weighted_sum(&self, weights: &[i64]) -> i64 {
+        self.states().iter().zip(weights).map(|(s, w)| s.id() as i64 * w).sum()
+    }
+
+    /// Whether any neighbour is in `state`
+    fn any(&self, state: State) -> bool {
+        self.states().contains(&state)
+    }
+
+    /// Whether every neighbour is in `state`, true if there are none
+    fn all(&self, state: State) -> bool {
+        self.states().iter().all(|s| *s == state)
+    }
+}
+
+impl Reduce for [State] {
+    fn states(&self) -> &[State] {
+        self
+    }
+}
+
+impl<const N: usize> Reduce for [State; N] {
+    fn states(&self) -> &[State] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce_test() {
+        let [a, b, c] = [0, 1, 4].map(State::new);
+        let n: &[State] = &[b, a, c, b];
+        assert_eq!(n.nth(2), Some(c));
+        assert_eq!(n.nth(4), None);
+        assert_eq!((n.count(b), n.count_any(&[a, c])), (2, 2));
+        assert_eq!((n.min_state(), n.max_state()), (Some(a), Some(c)));
+        assert_eq!(n.sum(), 6);
+        assert_eq!(n.weighted_sum(&[10, 10, -1]), 6);
+        assert!(n.any(c) && !n.all(b));
+        assert_eq!(n.histogram().iter().collect::<Vec<_>>(), vec![(a, 1), (b, 2), (c, 1)]);
+
+        let empty: &[State] = &[];
+        assert_eq!((empty.min_state(), empty.histogram().most_common()), (None, None));
+        assert!(empty.all(a) && !empty.any(a));
+    }
+}
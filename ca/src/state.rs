@@ -1,26 +1,324 @@
-/// A state in an automotan
-pub struct State<'a, T>{
-    name: &'a str,
-    val: T
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// A state in an automaton, a compact id whose name is kept in a [`StateSet`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State(u8);
+
+impl State {
+    /// Return the state with an id
+    pub const fn new(id: u8) -> State {
+        State(id)
+    }
+
+    /// Get state id, also used as its value
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+
+    /// Get state name from the set it belongs to
+    pub fn name<'a>(&self, states: &'a StateSet) -> Option<&'a str> {
+        states.name(*self)
+    }
+}
+
+impl From<u8> for State {
+    fn from(id: u8) -> Self {
+        State(id)
+    }
+}
+
+impl From<State> for u8 {
+    fn from(state: State) -> Self {
+        state.0
+    }
 }
 
-impl<'a, T> State<'a, T>  {
-    /// Return a new, named state with a value
-    pub fn new(name: &'a str, val: T) -> State<'a, T> {
-        State {
+/// Colour of a state as red, green and blue components
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parse a colour written as `#rrggbb`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ca::state::Rgb;
+    /// assert_eq!(Rgb::from_hex("#ff8000"), Some(Rgb(255, 128, 0)));
+    /// assert_eq!(Rgb::from_hex("ff8000"), None);
+    /// ```
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        // from_str_radix would also accept a sign
+        let digits = hex.strip_prefix('#').filter(|d| d.len() == 6 && d.bytes().all(|b| b.is_ascii_hexdigit()))?;
+        let component = |i| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some(Rgb(component(0)?, component(2)?, component(4)?))
+    }
+
+    /// Colour from a hue in degrees at full saturation and value
+    fn from_hue(hue: u32) -> Rgb {
+        let hue = hue % 360;
+        let x = (255 * (60 - (hue % 120).abs_diff(60)) / 60) as u8;
+        match hue / 60 {
+            0 => Rgb(255, x, 0),
+            1 => Rgb(x, 255, 0),
+            2 => Rgb(0, 255, x),
+            3 => Rgb(0, x, 255),
+            4 => Rgb(x, 0, 255),
+            _ => Rgb(255, 0, x),
+        }
+    }
+}
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// How a state is displayed, every field is optional
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateInfo {
+    colour: Option<Rgb>,
+    glyph: Option<char>,
+    description: Option<String>,
+}
+
+impl StateInfo {
+    pub fn new() -> StateInfo {
+        StateInfo::default()
+    }
+
+    pub fn colour(&self) -> Option<Rgb> { self.colour }
+
+    pub fn glyph(&self) -> Option<char> { self.glyph }
+
+    pub fn description(&self) -> Option<&str> { self.description.as_deref() }
+
+    pub fn set_colour(&mut self, colour: Option<Rgb>) { self.colour = colour; }
+
+    pub fn set_glyph(&mut self, glyph: Option<char>) { self.glyph = glyph; }
+
+    pub fn set_description(&mut self, description: Option<String>) { self.description = description; }
+}
+
+/// A state in a [`StateSet`] along with its name and display information
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Entry {
+    state: State,
+    name: String,
+    info: StateInfo,
+}
+
+/// Registry of the named states of an automaton
+///
+/// # Examples
+///
+/// ```
+/// use ca::state::{State, StateSet};
+///
+/// let mut states = StateSet::new();
+/// let dead = states.push("dead").unwrap();
+/// let alive = states.push("alive").unwrap();
+/// assert_eq!(alive, State::new(1));
+/// assert_eq!(states.get("dead"), Some(dead));
+/// assert_eq!(alive.name(&states), Some("alive"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateSet {
+    states: Vec<Entry>,
+    ids: HashMap<String, State>,
+}
+
+impl StateSet {
+    /// Return a new, empty `StateSet`
+    pub fn new() -> StateSet {
+        StateSet {
+            states: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Add a state with the lowest unused id. `None` if the name is already
+    /// used or all 256 ids are taken
+    pub fn push(&mut self, name: impl Into<String>) -> Option<State> {
+        let id = (0..=u8::MAX).find(|id| self.name(State(*id)).is_none())?;
+        let state = State(id);
+        self.insert(name, state).then_some(state)
+    }
+
+    /// Add a state with a given id. Returns `false` and leaves the set
+    /// unchanged if the name or id is already used
+    pub fn insert(&mut self, name: impl Into<String>, state: State) -> bool {
+        let name = name.into();
+        if self.ids.contains_key(&name) || self.name(state).is_some() {
+            return false;
+        }
+        self.ids.insert(name.clone(), state);
+        self.states.push(Entry {
+            state,
             name,
-            val,
+            info: StateInfo::default(),
+        });
+        true
+    }
+
+    /// Get the state with a name
+    pub fn get(&self, name: &str) -> Option<State> {
+        self.ids.get(name).copied()
+    }
+
+    /// Get the name of a state
+    pub fn name(&self, state: State) -> Option<&str> {
+        self.entry(state).map(|e| e.name.as_str())
+    }
+
+    /// Get the display information of a state
+    pub fn info(&self, state: State) -> Option<&StateInfo> {
+        self.entry(state).map(|e| &e.info)
+    }
+
+    pub fn info_mut(&mut self, state: State) -> Option<&mut StateInfo> {
+        self.states.iter_mut().find(|e| e.state == state).map(|e| &mut e.info)
+    }
+
+    /// Colour to draw a state with. States without one are black for state
+    /// 0, white for state 1 and spread around the colour wheel after that
+    pub fn colour(&self, state: State) -> Rgb {
+        if let Some(colour) = self.info(state).and_then(|i| i.colour) {
+            return colour;
         }
+        match state.0 {
+            0 => Rgb(0, 0, 0),
+            1 => Rgb(255, 255, 255),
+            id => Rgb::from_hue((id as u32 - 2) * 137),
+        }
+    }
+
+    /// Character to print a state as. States without one are `.` for state 0
+    /// and their id in base 36 after that, or `?` if it doesn't fit
+    pub fn glyph(&self, state: State) -> char {
+        if let Some(glyph) = self.info(state).and_then(|i| i.glyph) {
+            return glyph;
+        }
+        match state.0 {
+            0 => '.',
+            id => char::from_digit(id as u32, 36).unwrap_or('?'),
+        }
+    }
+
+    fn entry(&self, state: State) -> Option<&Entry> {
+        self.states.iter().find(|e| e.state == state)
+    }
+
+    pub fn contains(&self, state: State) -> bool {
+        self.name(state).is_some()
+    }
+
+    /// Number of states in the set
+    pub fn len(&self) -> usize {
+        self.states.len()
     }
 
-    /// Get state value
-    pub fn val(&self) -> &T {
-        &self.val
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
     }
 
-    /// Get state name
-    pub fn name(&self) -> &str {
-        self.name
+    /// One more than the largest id, so every state is in `0..n_states`
+    pub fn n_states(&self) -> usize {
+        self.states.iter().map(|e| e.state.0 as usize + 1).max().unwrap_or(0)
+    }
+
+    /// Iterate over the states and their names in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (State, &str)> {
+        self.states.iter().map(|e| (e.state, e.name.as_str()))
+    }
+}
+
+/// States are written as a list of their ids, names and display information
+#[cfg(feature = "serde")]
+impl serde::Serialize for StateSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.states.iter())
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StateSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut states = StateSet::new();
+        for entry in <Vec<Entry> as serde::Deserialize>::deserialize(deserializer)? {
+            if !states.insert(entry.name.clone(), entry.state) {
+                return Err(serde::de::Error::custom(format!("state {:?} or its id is repeated", entry.name)));
+            }
+            if let Some(info) = states.info_mut(entry.state) {
+                *info = entry.info;
+            }
+        }
+        Ok(states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_test() {
+        let mut states = StateSet::new();
+        assert!(states.insert("off", State::new(0)));
+        assert!(states.insert("on", State::new(5)));
+        assert!(!states.insert("on", State::new(2)));
+        assert!(!states.insert("other", State::new(5)));
+        assert_eq!(states.push("dying"), Some(State::new(1)));
+        assert_eq!(states.push("off"), None);
+        assert_eq!(states.len(), 3);
+        assert_eq!(states.n_states(), 6);
+        let names: Vec<_> = states.iter().map(|(_, n)| n).collect();
+        assert_eq!(names, vec!["off", "on", "dying"]);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut states = StateSet::new();
+        for i in 0..=255 {
+            states.push(format!("s{}", i)).unwrap();
+        }
+        assert_eq!(states.push("full"), None);
+        for (state, name) in states.iter() {
+            assert_eq!(states.get(name), Some(state));
+            assert_eq!(state.name(&states), Some(name));
+        }
+    }
+
+    #[test]
+    fn info_test() {
+        let mut states = StateSet::new();
+        let off = states.push("off").unwrap();
+        let on = states.push("on").unwrap();
+        let info = states.info_mut(on).unwrap();
+        info.set_colour(Rgb::from_hex("#ffcc00"));
+        info.set_glyph(Some('█'));
+        info.set_description(Some("a live cell".into()));
+        assert!(states.info_mut(State::new(7)).is_none());
+
+        assert_eq!(states.info(on).and_then(|i| i.description()), Some("a live cell"));
+        assert_eq!((states.colour(on), states.glyph(on)), (Rgb(255, 204, 0), '█'));
+        assert_eq!((states.colour(off), states.glyph(off)), (Rgb(0, 0, 0), '.'));
+        assert_eq!(states.glyph(State::new(12)), 'c');
+        assert_eq!(states.colour(State::new(2)), Rgb(255, 0, 0));
+        assert_eq!(states.colour(on).to_string(), "#ffcc00");
+    }
+
+    #[test]
+    fn from_hex_test() {
+        assert_eq!(Rgb::from_hex("#00FFa0"), Some(Rgb(0, 255, 160)));
+        assert_eq!(Rgb::from_hex("#+f+f+f"), None);
+        assert_eq!(Rgb::from_hex("#fffff"), None);
+        assert_eq!(Rgb::from_hex("#ff ff0"), None);
+    }
+}